@@ -1,12 +1,43 @@
-use std::fs::File;
-use std::io::Read;
-use std::io::Write;
+use std::cmp::{max, min};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+
+use encoding_rs::{Encoding, UTF_8};
+use memmap::Mmap;
 /*
  * test
  * */
 use cursor::Cursor;
+use error::{Error, Result};
+
+#[cfg(test)]
+mod bench;
+mod encoding;
+mod file_format;
+mod hex;
+mod history;
+mod loader;
+mod piece_tree;
+
+use self::encoding::EncodingWriter;
+pub use self::file_format::FileFormat;
+use self::file_format::NewlineWriter;
+use self::hex::ByteWriter;
+pub use self::hex::BYTES_PER_ROW;
+use self::history::History;
+use self::loader::Loaded;
+use self::piece_tree::{PieceTree, Snapshot};
+
+// files at least this large are mapped and scanned in the background
+const MAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+// mapped files are scanned only for '\n' as UTF-8, so this head of them
+// must be UTF-8 without BOM and '\r'
+const DETECTION_BYTES: usize = 64 * 1024;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BufferId(usize);
 
 use std::sync::RwLock;
@@ -24,303 +55,503 @@ impl BufferId {
 }
 
 #[derive(Debug, Clone)]
-pub struct Original {
-    pub start: usize,
-    pub length: usize,
+pub struct Buffer {
+    path: Option<String>,
+    text: PieceTree,
+    history: History,
+    // the text when the buffer was opened or saved, to which undo may go back
+    saved: Snapshot,
+    // the encoding, BOM or line endings are changed
+    is_option_modified: bool,
+    file_format: FileFormat,
+    has_final_newline: bool,
+    encoding: &'static Encoding,
+    has_bom: bool,
+    // shown as hex dump, one char per byte
+    is_binary: bool,
+    loader: Option<Rc<Receiver<Loaded>>>,
+    // the rest of the file could not be loaded
+    is_truncated: bool,
 }
 
-#[derive(Debug, Clone)]
-pub enum Piece {
-    Original(usize, usize), // (pos, length)
-    Add(String),
+fn make_buffer_from_string(text: String) -> Buffer {
+    let file_format = FileFormat::detect(&text);
+    let mut text = file_format.normalize(text);
+    // the newline at the end of the file does not begin a new line
+    let has_final_newline = text.ends_with('\n');
+    if has_final_newline {
+        text.pop();
+    }
+    let text = PieceTree::new(text);
+    Buffer {
+        path: None,
+        saved: text.snapshot(),
+        text: text,
+        history: History::default(),
+        is_option_modified: false,
+        file_format: file_format,
+        has_final_newline: has_final_newline,
+        encoding: UTF_8,
+        has_bom: false,
+        is_binary: false,
+        loader: None,
+        is_truncated: false,
+    }
 }
 
-impl Piece {
-    fn is_original(&self) -> bool {
-        match self {
-            Piece::Original(_, _) => true,
-            _ => false,
+impl Buffer {
+    // `encoding` ... the encoding of the file, detected if `None`
+    pub fn from_file(filename: &str, encoding: Option<&'static Encoding>) -> Result<Self> {
+        let mut file = File::open(filename).map_err(|e| Error::open(filename, e))?;
+        let metadata = file.metadata().map_err(|e| Error::read(filename, e))?;
+        if metadata.len() >= MAP_THRESHOLD && encoding.map_or(true, |e| e == UTF_8) {
+            let mmap = unsafe { Mmap::map(&file) }.map_err(|e| Error::read(filename, e))?;
+            let head = &mmap[..min(mmap.len(), DETECTION_BYTES)];
+            // a char may be cut at the end of the head
+            let is_utf8 = match ::std::str::from_utf8(head) {
+                Ok(_) => true,
+                Err(err) => err.error_len().is_none(),
+            };
+            if is_utf8
+                && Encoding::for_bom(head).is_none()
+                && !head.contains(&b'\r')
+                && !hex::is_binary(head)
+            {
+                let mmap = Arc::new(mmap);
+                let mut buffer = Buffer::empty();
+                buffer.has_final_newline = mmap.last() == Some(&b'\n');
+                buffer.text = PieceTree::mapped(mmap.clone());
+                buffer.loader = Some(Rc::new(loader::spawn(mmap)));
+                buffer.path = Some(filename.to_string());
+                return Ok(buffer);
+            }
         }
-    }
-    fn is_add(&self) -> bool {
-        match self {
-            Piece::Add(_) => true,
-            _ => false,
+
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)
+            .map_err(|e| Error::read(filename, e))?;
+        if encoding.is_none() && hex::is_binary(&bytes) {
+            let mut buffer = Buffer::empty();
+            buffer.text = PieceTree::new(hex::decode(&bytes));
+            buffer.has_final_newline = false;
+            buffer.is_binary = true;
+            buffer.path = Some(filename.to_string());
+            return Ok(buffer);
         }
+        let (text, encoding, has_bom) = encoding::decode(&bytes, encoding)
+            .map_err(|encoding| Error::Encoding(filename.to_string(), encoding.name()))?;
+
+        let mut buffer = make_buffer_from_string(text);
+        buffer.path = Some(filename.to_string());
+        buffer.encoding = encoding;
+        buffer.has_bom = has_bom;
+        Ok(buffer)
     }
 
-    fn length(&self) -> usize {
-        match self {
-            Piece::Original(_, length) => *length,
-            Piece::Add(ref str) => str.len() as usize,
+    // opens `filename`, or an empty buffer bound to it if it does not exist yet
+    pub fn open(filename: &str, encoding: Option<&'static Encoding>) -> Result<Self> {
+        match Buffer::from_file(filename, encoding) {
+            Err(Error::NotFound(_)) => {
+                let mut buffer = Buffer::empty();
+                buffer.path = Some(filename.to_string());
+                if let Some(encoding) = encoding {
+                    buffer.set_encoding(encoding);
+                    buffer.is_option_modified = false;
+                }
+                Ok(buffer)
+            }
+            result => result,
         }
     }
 
-    fn split(self, pos: usize) -> (Piece, Piece) {
-        match self {
-            Piece::Original(start, length) => (
-                Piece::Original(start, pos),
-                Piece::Original(start + pos, length - pos),
-            ),
-            Piece::Add(mut left) => {
-                let right = left.split_off(pos as usize);
-                (Piece::Add(left), Piece::Add(right))
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_ref().map(|path| path.as_str())
+    }
+
+    pub fn name(&self) -> &str {
+        self.path().unwrap_or("[No Name]")
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    // takes the pieces scanned so far. returns whether the text grew
+    pub fn poll_loading(&mut self) -> Result<bool> {
+        let mut is_grown = false;
+        while let Some(loader) = self.loader.clone() {
+            match loader.try_recv() {
+                Ok(loaded) => is_grown |= self.apply_loaded(loaded)?,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.loader = None,
             }
         }
+        Ok(is_grown)
     }
 
-    fn pop(self) -> Option<Piece> {
-        match self {
-            Piece::Original(_, 1) => None,
-            Piece::Original(start, length) => Some(Piece::Original(start, length - 1)),
-            Piece::Add(ref str) if str.len() == 1 => None,
-            Piece::Add(mut str) => {
-                str.pop();
-                Some(Piece::Add(str))
+    fn finish_loading(&mut self) -> Result<()> {
+        while let Some(loader) = self.loader.clone() {
+            match loader.recv() {
+                Ok(loaded) => {
+                    self.apply_loaded(loaded)?;
+                }
+                Err(_) => self.loader = None,
             }
         }
+        Ok(())
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct Buffer {
-    data: Vec<char>,
-    piece_tables: Vec<Vec<Piece>>,
-}
+    fn apply_loaded(&mut self, loaded: Loaded) -> Result<bool> {
+        match loaded {
+            Loaded::Pieces(pieces) => {
+                let is_grown = !pieces.is_empty();
+                // the loaded text is not a change
+                let is_saved = self.text.is_at(&self.saved);
+                self.text.append_loaded(pieces);
+                if is_saved {
+                    self.saved = self.text.snapshot();
+                }
+                Ok(is_grown)
+            }
+            Loaded::Done => {
+                self.loader = None;
+                Ok(false)
+            }
+            Loaded::Invalid => {
+                self.loader = None;
+                self.is_truncated = true;
+                Err(Error::Encoding(self.name().to_string(), UTF_8.name()))
+            }
+        }
+    }
 
-fn split_line_pieces(line: Vec<Piece>, split_pos: usize) -> (Vec<Piece>, Vec<Piece>) {
-    let mut current_pos = 0;
-    let mut left = vec![];
-    let mut right = vec![];
-    for piece in line {
-        let piece_length = piece.length();
-        if current_pos <= split_pos && split_pos < current_pos + piece_length {
-            let (left_, right_) = piece.split(split_pos - current_pos);
-            left.push(left_);
-            right.push(right_);
-        } else if current_pos < split_pos {
-            left.push(piece)
+    pub fn save_as(&mut self, filename: &str) -> Result<()> {
+        self.finish_loading()?;
+        // saving would lose the part which could not be loaded
+        if self.is_truncated {
+            return Err(Error::Encoding(self.name().to_string(), UTF_8.name()));
+        }
+        // the mapped file is still read, so it is replaced instead of truncated
+        if self.text.is_mapped() {
+            let temp = format!("{}.ysd-tmp", filename);
+            let file = File::create(&temp).map_err(|e| Error::open(filename, e))?;
+            if let Err(err) = self.write_contents(BufWriter::new(file)) {
+                let _ = fs::remove_file(&temp);
+                return Err(Error::write(filename, err));
+            }
+            // the replaced file keeps its mode
+            let result = match fs::metadata(filename) {
+                Ok(metadata) => fs::set_permissions(&temp, metadata.permissions()),
+                Err(_) => Ok(()),
+            };
+            if let Err(err) = result.and_then(|_| fs::rename(&temp, filename)) {
+                let _ = fs::remove_file(&temp);
+                return Err(Error::write(filename, err));
+            }
         } else {
-            right.push(piece)
+            // encoded before the file is truncated, since some chars may not be encodable
+            let mut bytes = vec![];
+            self.write_contents(&mut bytes)
+                .map_err(|e| Error::write(filename, e))?;
+            let mut file = File::create(filename).map_err(|e| Error::open(filename, e))?;
+            file.write_all(&bytes)
+                .map_err(|e| Error::write(filename, e))?;
         }
-        current_pos += piece_length;
+        self.saved = self.text.snapshot();
+        self.is_option_modified = false;
+        // `:write` saves to the file from now on
+        self.path = Some(filename.to_string());
+        Ok(())
     }
-    (left, right)
-}
 
-fn make_buffer_from_string(text: String) -> Buffer {
-    let data: Vec<char> = text.chars().collect();
-    let mut piece_tables: Vec<Vec<Piece>> = vec![];
-    let mut piece = Piece::Original(0, 0);
-
-    for (pos, c) in data.iter().enumerate() {
-        if *c == '\n' {
-            piece_tables.push(vec![piece]);
-            piece = Piece::Original(pos + 1, 0);
-        } else {
-            piece = if let Piece::Original(pos, length) = piece {
-                Piece::Original(pos, length + 1)
-            } else {
-                unreachable!()
-            };
+    fn write_contents(&self, mut out: impl Write) -> io::Result<()> {
+        if self.is_binary {
+            let mut out = ByteWriter::new(out);
+            self.text.write_to(&mut out)?;
+            return out.flush();
+        }
+        if self.has_bom {
+            out.write_all(encoding::bom(self.encoding))?;
         }
+        let out = EncodingWriter::new(out, self.encoding);
+        let mut out = NewlineWriter::new(out, self.file_format);
+        self.text.write_to(&mut out)?;
+        if self.has_final_newline {
+            writeln!(out)?;
+        }
+        out.get_mut().finish()
     }
 
-    Buffer {
-        data: data,
-        piece_tables: piece_tables,
+    pub fn empty() -> Self {
+        let mut buffer = make_buffer_from_string(String::new());
+        buffer.has_final_newline = true;
+        buffer
     }
-}
 
-impl Buffer {
-    pub fn from_file(filename: &str) -> Self {
-        let mut text = String::new();
-        File::open(filename)
-            .and_then(|mut f| f.read_to_string(&mut text))
-            .expect("can not open file");
-
-        make_buffer_from_string(text)
-    }
-
-    pub fn save_as(&self, filename: &str) -> Result<(), String> {
-        // TODO: remove unwrap
-        let mut file = File::create(filename).unwrap();
-        for piece_table in self.piece_tables.iter() {
-            for piece in piece_table.iter() {
-                match piece {
-                    Piece::Original(start, length) => {
-                        let word = &self.data[*start..(*start + *length)];
-                        let word: String = word.iter().collect();
-                        write!(file, "{}", word).unwrap()
-                    }
-                    Piece::Add(ref str) => write!(file, "{}", str).unwrap(),
-                }
-            }
+    pub fn is_binary(&self) -> bool {
+        self.is_binary
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+    // the text is encoded when the buffer is saved
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        if self.encoding != encoding {
+            self.encoding = encoding;
+            // UTF-16 can not be detected without BOM
+            self.has_bom = encoding::bom(encoding).len() == 2;
+            self.is_option_modified = true;
         }
-        file.flush().unwrap();
-        Ok(())
     }
 
-    pub fn empty() -> Self {
-        Buffer {
-            data: vec![],
-            piece_tables: vec![vec![]],
+    pub fn has_bom(&self) -> bool {
+        self.has_bom
+    }
+    // only UTF-8 and UTF-16 have BOM
+    pub fn can_have_bom(&self) -> bool {
+        !encoding::bom(self.encoding).is_empty()
+    }
+    pub fn set_bom(&mut self, has_bom: bool) {
+        if self.has_bom != has_bom {
+            self.has_bom = has_bom;
+            self.is_option_modified = true;
+        }
+    }
+
+    pub fn file_format(&self) -> FileFormat {
+        self.file_format
+    }
+    // line endings are converted when the buffer is saved
+    pub fn set_file_format(&mut self, file_format: FileFormat) {
+        if self.file_format != file_format {
+            self.file_format = file_format;
+            self.is_option_modified = true;
         }
     }
 
-    pub fn line_number(n_lines: usize) -> Self {
-        let width = n_lines.to_string().len();
-        Buffer {
-            data: vec![],
-            piece_tables: (0..n_lines)
-                .into_iter()
-                .map(|n| vec![Piece::Add(format!(" {:width$} ", n + 1, width = width))])
-                .collect(),
+    pub fn set_final_newline(&mut self, has_final_newline: bool) {
+        if self.has_final_newline != has_final_newline {
+            self.has_final_newline = has_final_newline;
+            self.is_option_modified = true;
         }
     }
 
-    pub fn status_buffer() -> ((Buffer, BufferId), (Buffer, BufferId)) {
+    // width of line numbers for a buffer of `n_lines`
+    pub fn line_number_width(n_lines: usize) -> usize {
+        n_lines.to_string().len() + 2
+    }
+
+    pub fn status_buffer() -> ((Buffer, BufferId), (Buffer, BufferId), (Buffer, BufferId)) {
         (
             (Buffer::empty(), BufferId::new()),
             (Buffer::empty(), BufferId::new()),
+            (Buffer::empty(), BufferId::new()),
         )
     }
 
+    pub fn is_modified(&self) -> bool {
+        self.is_option_modified || !self.text.is_at(&self.saved)
+    }
+
+    // lines of binary buffers are rows of the hex view
     pub fn height(&self) -> usize {
-        self.piece_tables.len()
+        if self.is_binary {
+            max(1, (self.text.chars() + BYTES_PER_ROW - 1) / BYTES_PER_ROW)
+        } else {
+            self.text.lines()
+        }
     }
-    pub fn line_at(&self, line_i: usize) -> Vec<char> {
-        let mut result = vec![];
-        for piece in self.piece_tables.get(line_i).unwrap() {
-            match piece {
-                Piece::Original(start, length) => {
-                    let mut target: Vec<char> = self.data[*start..(*start + *length)]
-                        .iter()
-                        .cloned()
-                        .collect();
-                    result.append(&mut target)
-                }
-                Piece::Add(ref str) => {
-                    let mut target: Vec<char> = str.chars().clone().collect();
-                    result.append(&mut target)
-                }
-            }
+
+    // char positions of the beginning and the end (excluding the newline) of a line
+    fn line_range(&self, line_i: usize) -> (usize, usize) {
+        if self.is_binary {
+            let len = self.text.chars();
+            let start = min(line_i * BYTES_PER_ROW, len);
+            return (start, min(start + BYTES_PER_ROW, len));
         }
-        result
+        let start = self.text.line_start(line_i);
+        let end = if line_i + 1 < self.height() {
+            self.text.line_start(line_i + 1) - 1
+        } else {
+            self.text.chars()
+        };
+        (start, end)
+    }
+
+    pub fn line_at(&self, line_i: usize) -> Vec<char> {
+        let (start, end) = self.line_range(line_i);
+        self.text.slice(start, end).chars().collect()
     }
     pub fn line_width_at(&self, line_i: usize) -> usize {
-        let mut result: usize = 0;
-        for piece in self.piece_tables.get(line_i).unwrap() {
-            result += piece.length();
-        }
-        result
+        let (start, end) = self.line_range(line_i);
+        end - start
     }
 
+    // for buffers without history, e.g. status buffers
     pub fn clear(&mut self) -> &mut Self {
-        self.piece_tables = vec![vec![Piece::Original(0, 0)]];
+        self.text = PieceTree::new(String::new());
+        self.history = History::default();
+        self.saved = self.text.snapshot();
         self
     }
     pub fn push(&mut self, word: String) -> &mut Self {
-        let last = self.piece_tables.last_mut().unwrap();
-        last.push(Piece::Add(word));
+        let end = self.text.chars();
+        self.text.insert(end, word.as_str());
+        self.saved = self.text.snapshot();
         self
     }
 
-    pub fn insert_line_at_cursor(&mut self, cursor: &Cursor) {
-        if cursor.x >= self.line_width_at(cursor.y) {
-            self.piece_tables
-                .insert(cursor.y, vec![Piece::Original(0, 0)]);
+    fn edit<F>(&mut self, cursor: &Cursor, f: F)
+    where
+        F: FnOnce(&mut PieceTree),
+    {
+        // the rest of the file is joined at the end of the text
+        if self.is_loading() {
             return;
         }
+        let before = self.text.snapshot();
+        f(&mut self.text);
+        self.history.record(before, cursor);
+    }
+
+    // char position of `cursor`, which may be after the end of the line
+    fn position_of(&self, cursor: &Cursor) -> usize {
+        let (start, end) = self.line_range(cursor.y);
+        start + min(cursor.x, end - start)
+    }
+
+    // replaces the line `line_i` with `text`, which may span several lines
+    pub fn replace_line(&mut self, line_i: usize, text: &str, cursor: &Cursor) {
+        let (start, end) = self.line_range(line_i);
+        self.edit(cursor, |tree| {
+            tree.remove(start, end);
+            tree.insert(start, text);
+        });
+    }
+
+    pub fn text_between(&self, start: &Cursor, end: &Cursor) -> String {
+        self.text
+            .slice(self.position_of(start), self.position_of(end))
+    }
+
+    // replaces the text from `start` up to `end` with `text`
+    pub fn replace_between(&mut self, start: &Cursor, end: &Cursor, text: &str, cursor: &Cursor) {
+        let (start, end) = (self.position_of(start), self.position_of(end));
+        self.edit(cursor, |tree| {
+            tree.remove(start, end);
+            tree.insert(start, text);
+        });
+    }
+
+    pub fn begin_undo_group(&mut self, cursor: &Cursor) {
+        self.history.begin_group(cursor);
+    }
+    pub fn end_undo_group(&mut self) {
+        self.history.end_group();
+    }
+
+    // returns the cursor position at the time the undone edit started
+    pub fn undo(&mut self) -> Option<Cursor> {
+        let step = self.history.pop_undo(self.text.snapshot())?;
+        self.text.restore(step.snapshot);
+        Some(step.cursor)
+    }
+    pub fn redo(&mut self) -> Option<Cursor> {
+        let step = self.history.pop_redo(self.text.snapshot())?;
+        self.text.restore(step.snapshot);
+        Some(step.cursor)
+    }
 
-        let line = ::std::mem::replace(self.piece_tables.get_mut(cursor.y).unwrap(), vec![]);
-        let (left, mut right) = split_line_pieces(line, cursor.x);
-        self.piece_tables
-            .get_mut(cursor.y)
-            .unwrap()
-            .append(&mut right);
-        self.piece_tables.insert(cursor.y, left);
+    pub fn insert_line_at_cursor(&mut self, cursor: &Cursor) {
+        let pos = self.position_of(cursor);
+        self.edit(cursor, |tree| tree.insert(pos, "\n"));
     }
 
     pub fn insert_at_cursor(&mut self, c: char, cursor: &Cursor) {
-        let line_width = self.line_width_at(cursor.y);
-        let insert_x = if cursor.x >= line_width {
-            line_width
-        } else {
-            cursor.x
-        };
+        let pos = self.position_of(cursor);
+        self.edit(cursor, |tree| tree.insert(pos, c.encode_utf8(&mut [0; 4])));
+    }
 
-        let line = ::std::mem::replace(self.piece_tables.get_mut(cursor.y).unwrap(), vec![]);
-        let mut current_pos = 0;
-        for piece in line {
-            let ref mut line = self.piece_tables.get_mut(cursor.y).unwrap();
-            let piece_length = piece.length();
-            if current_pos == insert_x && piece.is_original() {
-                if let Some(Piece::Add(ref mut str)) = line.last_mut() {
-                    str.push(c);
-                } else {
-                    line.push(Piece::Add(format!("{}", c)));
-                }
-                line.push(piece);
-            } else if current_pos <= insert_x && insert_x <= current_pos + piece_length {
-                match piece {
-                    Piece::Original(_, _) => {
-                        let (left, right) = piece.split(insert_x - current_pos);
-                        let ref mut line = self.piece_tables.get_mut(cursor.y).unwrap();
-                        if left.length() != 0 {
-                            line.push(left);
-                        }
-                        line.push(Piece::Add(format!("{}", c)));
-                        if right.length() != 0 {
-                            line.push(right);
-                        }
-                    }
-                    Piece::Add(mut str) => {
-                        str.insert(insert_x - current_pos, c);
-                        line.push(Piece::Add(str));
-                    }
-                }
-            } else {
-                line.push(piece);
-            }
-            current_pos += piece_length;
+    // overwrites the byte at `cursor` of a binary buffer
+    pub fn replace_byte_at_cursor(&mut self, byte: u8, cursor: &Cursor) {
+        if cursor.x >= self.line_width_at(cursor.y) {
+            return;
         }
+        let pos = self.position_of(cursor);
+        let c = byte as char;
+        self.edit(cursor, |tree| {
+            tree.remove(pos, pos + 1);
+            tree.insert(pos, c.encode_utf8(&mut [0; 4]));
+        });
     }
 
     pub fn erase_at_cursor(&mut self, cursor: &Cursor) {
-        let line_width = self.line_width_at(cursor.y);
-        let erase_x = if cursor.x >= line_width {
-            line_width
-        } else {
-            cursor.x
-        };
-
-        let line = ::std::mem::replace(self.piece_tables.get_mut(cursor.y).unwrap(), vec![]);
-        let mut current_pos = 0;
-        for piece in line {
-            let ref mut line = self.piece_tables.get_mut(cursor.y).unwrap();
-            let piece_length = piece.length();
-            if current_pos <= erase_x && erase_x < current_pos + piece_length {
-                match piece {
-                    Piece::Original(_, _) => {
-                        let (left, right) = piece.split(erase_x - current_pos + 1);
-                        let ref mut line = self.piece_tables.get_mut(cursor.y).unwrap();
-                        if let Some(left) = left.pop() {
-                            line.push(left);
-                        }
-                        if right.length() != 0 {
-                            line.push(right);
-                        }
-                    }
-                    Piece::Add(mut str) => {
-                        str.remove(erase_x - current_pos);
-                        line.push(Piece::Add(str));
-                    }
-                }
-            } else {
-                line.push(piece);
-            }
-            current_pos += piece_length;
+        if cursor.x >= self.line_width_at(cursor.y) {
+            return;
         }
+        let pos = self.position_of(cursor);
+        self.edit(cursor, |tree| tree.remove(pos, pos + 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    use self::piece_tree::Summary;
+
+    #[test]
+    fn test_undo_to_saved_text() {
+        let mut buffer = make_buffer_from_string("ab\ncd\n".to_string());
+        let cursor = Cursor { x: 1, y: 0 };
+        assert!(!buffer.is_modified());
+        buffer.insert_at_cursor('x', &cursor);
+        buffer.insert_line_at_cursor(&cursor);
+        assert!(buffer.is_modified());
+        buffer.undo();
+        assert!(buffer.is_modified());
+        buffer.undo();
+        assert!(!buffer.is_modified());
+        buffer.redo();
+        assert!(buffer.is_modified());
+
+        // options are not undone
+        buffer.undo();
+        buffer.set_file_format(FileFormat::Dos);
+        assert!(buffer.is_modified());
+    }
+
+    #[test]
+    fn test_edit_while_loading() {
+        let path = ::std::env::temp_dir().join(format!("ysd-loading-{}", ::std::process::id()));
+        fs::write(&path, "ab\ncd\n").unwrap();
+        let mmap = unsafe { Mmap::map(&File::open(&path).unwrap()) }.unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let (sender, receiver) = channel();
+        let mut buffer = Buffer::empty();
+        buffer.text = PieceTree::mapped(Arc::new(mmap));
+        buffer.loader = Some(Rc::new(receiver));
+        sender
+            .send(Loaded::Pieces(vec![(0, Summary::of("ab\nc"))]))
+            .unwrap();
+        buffer.poll_loading().unwrap();
+
+        // the tail is refused, as the rest of its line is not loaded yet
+        let cursor = Cursor { x: 1, y: 1 };
+        buffer.insert_at_cursor('x', &cursor);
+        sender
+            .send(Loaded::Pieces(vec![(4, Summary::of("d"))]))
+            .unwrap();
+        sender.send(Loaded::Done).unwrap();
+        buffer.poll_loading().unwrap();
+        assert!(!buffer.is_loading());
+        assert_eq!(buffer.line_at(1), vec!['c', 'd']);
+        assert!(!buffer.is_modified());
+
+        buffer.insert_at_cursor('x', &cursor);
+        assert_eq!(buffer.line_at(1), vec!['c', 'x', 'd']);
     }
 }
@@ -0,0 +1,334 @@
+// run with `cargo bench`. `LineTable` is the storage used before `PieceTree`:
+// one `Vec` of pieces per line over the whole file as `Vec<char>`, with the
+// added text kept in the pieces, and each edit copying the lines it changes for undo
+use test::{black_box, Bencher};
+
+use super::make_buffer_from_string;
+use cursor::Cursor;
+
+const N_LINES: usize = 200_000;
+
+fn text() -> String {
+    (0..N_LINES)
+        .map(|i| format!("{:08} let x = \"ゆしど\"; // some comment\n", i))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+enum Piece {
+    Original(usize, usize), // (pos, length)
+    Add(String),
+}
+
+fn byte_index(str: &str, char_pos: usize) -> usize {
+    str.char_indices()
+        .nth(char_pos)
+        .map(|(i, _)| i)
+        .unwrap_or(str.len())
+}
+
+impl Piece {
+    fn is_original(&self) -> bool {
+        match self {
+            Piece::Original(_, _) => true,
+            _ => false,
+        }
+    }
+
+    fn length(&self) -> usize {
+        match self {
+            Piece::Original(_, length) => *length,
+            Piece::Add(ref str) => str.chars().count(),
+        }
+    }
+
+    fn split(self, pos: usize) -> (Piece, Piece) {
+        match self {
+            Piece::Original(start, length) => (
+                Piece::Original(start, pos),
+                Piece::Original(start + pos, length - pos),
+            ),
+            Piece::Add(mut left) => {
+                let right = left.split_off(byte_index(&left, pos));
+                (Piece::Add(left), Piece::Add(right))
+            }
+        }
+    }
+
+    fn pop(self) -> Option<Piece> {
+        match self {
+            Piece::Original(_, 1) => None,
+            Piece::Original(start, length) => Some(Piece::Original(start, length - 1)),
+            Piece::Add(ref str) if str.chars().count() == 1 => None,
+            Piece::Add(mut str) => {
+                str.pop();
+                Some(Piece::Add(str))
+            }
+        }
+    }
+}
+
+fn split_line_pieces(line: Vec<Piece>, split_pos: usize) -> (Vec<Piece>, Vec<Piece>) {
+    let mut current_pos = 0;
+    let mut left = vec![];
+    let mut right = vec![];
+    for piece in line {
+        let piece_length = piece.length();
+        if current_pos <= split_pos && split_pos < current_pos + piece_length {
+            let (left_, right_) = piece.split(split_pos - current_pos);
+            left.push(left_);
+            right.push(right_);
+        } else if current_pos < split_pos {
+            left.push(piece)
+        } else {
+            right.push(piece)
+        }
+        current_pos += piece_length;
+    }
+    (left, right)
+}
+
+struct LineTable {
+    data: Vec<char>,
+    piece_tables: Vec<Vec<Piece>>,
+}
+
+impl LineTable {
+    fn new(text: String) -> Self {
+        let data: Vec<char> = text.chars().collect();
+        let mut piece_tables: Vec<Vec<Piece>> = vec![];
+        let mut piece = Piece::Original(0, 0);
+        for (pos, c) in data.iter().enumerate() {
+            if *c == '\n' {
+                piece_tables.push(vec![piece]);
+                piece = Piece::Original(pos + 1, 0);
+            } else if let Piece::Original(pos, length) = piece {
+                piece = Piece::Original(pos, length + 1);
+            }
+        }
+        if piece_tables.is_empty() {
+            piece_tables.push(vec![]);
+        }
+        LineTable {
+            data: data,
+            piece_tables: piece_tables,
+        }
+    }
+
+    fn line_at(&self, line_i: usize) -> Vec<char> {
+        let mut result = vec![];
+        for piece in self.piece_tables[line_i].iter() {
+            match piece {
+                Piece::Original(start, length) => {
+                    result.extend_from_slice(&self.data[*start..*start + *length])
+                }
+                Piece::Add(ref str) => result.extend(str.chars()),
+            }
+        }
+        result
+    }
+
+    fn line_width_at(&self, line_i: usize) -> usize {
+        self.piece_tables[line_i].iter().map(Piece::length).sum()
+    }
+
+    // the copies are dropped here, they are not kept to bound the memory of benches
+    fn edit_lines<F>(&mut self, cursor: &Cursor, n_before: usize, n_after: usize, f: F)
+    where
+        F: FnOnce(&mut Self),
+    {
+        let line = cursor.y;
+        let before = self.piece_tables[line..line + n_before].to_vec();
+        f(self);
+        let after = self.piece_tables[line..line + n_after].to_vec();
+        black_box((before, after));
+    }
+
+    fn insert_line_at_cursor(&mut self, cursor: &Cursor) {
+        self.edit_lines(cursor, 1, 2, |table| {
+            if cursor.x >= table.line_width_at(cursor.y) {
+                table
+                    .piece_tables
+                    .insert(cursor.y, vec![Piece::Original(0, 0)]);
+                return;
+            }
+            let line = ::std::mem::replace(&mut table.piece_tables[cursor.y], vec![]);
+            let (left, mut right) = split_line_pieces(line, cursor.x);
+            table.piece_tables[cursor.y].append(&mut right);
+            table.piece_tables.insert(cursor.y, left);
+        });
+    }
+
+    fn insert_at_cursor(&mut self, c: char, cursor: &Cursor) {
+        self.edit_lines(cursor, 1, 1, |table| {
+            let insert_x = ::std::cmp::min(cursor.x, table.line_width_at(cursor.y));
+            let line = ::std::mem::replace(&mut table.piece_tables[cursor.y], vec![]);
+            let mut current_pos = 0;
+            for piece in line {
+                let line = &mut table.piece_tables[cursor.y];
+                let piece_length = piece.length();
+                if current_pos == insert_x && piece.is_original() {
+                    if let Some(Piece::Add(ref mut str)) = line.last_mut() {
+                        str.push(c);
+                    } else {
+                        line.push(Piece::Add(format!("{}", c)));
+                    }
+                    line.push(piece);
+                } else if current_pos <= insert_x && insert_x <= current_pos + piece_length {
+                    match piece {
+                        Piece::Original(_, _) => {
+                            let (left, right) = piece.split(insert_x - current_pos);
+                            if left.length() != 0 {
+                                line.push(left);
+                            }
+                            line.push(Piece::Add(format!("{}", c)));
+                            if right.length() != 0 {
+                                line.push(right);
+                            }
+                        }
+                        Piece::Add(mut str) => {
+                            let i = byte_index(&str, insert_x - current_pos);
+                            str.insert(i, c);
+                            line.push(Piece::Add(str));
+                        }
+                    }
+                } else {
+                    line.push(piece);
+                }
+                current_pos += piece_length;
+            }
+        });
+    }
+
+    fn erase_at_cursor(&mut self, cursor: &Cursor) {
+        self.edit_lines(cursor, 1, 1, |table| {
+            let erase_x = ::std::cmp::min(cursor.x, table.line_width_at(cursor.y));
+            let line = ::std::mem::replace(&mut table.piece_tables[cursor.y], vec![]);
+            let mut current_pos = 0;
+            for piece in line {
+                let line = &mut table.piece_tables[cursor.y];
+                let piece_length = piece.length();
+                if current_pos <= erase_x && erase_x < current_pos + piece_length {
+                    match piece {
+                        Piece::Original(_, _) => {
+                            let (left, right) = piece.split(erase_x - current_pos + 1);
+                            if let Some(left) = left.pop() {
+                                line.push(left);
+                            }
+                            if right.length() != 0 {
+                                line.push(right);
+                            }
+                        }
+                        Piece::Add(mut str) => {
+                            let i = byte_index(&str, erase_x - current_pos);
+                            str.remove(i);
+                            line.push(Piece::Add(str));
+                        }
+                    }
+                } else {
+                    line.push(piece);
+                }
+                current_pos += piece_length;
+            }
+        });
+    }
+}
+
+#[bench]
+fn open_line_table(b: &mut Bencher) {
+    let text = text();
+    b.iter(|| black_box(LineTable::new(text.clone())));
+}
+
+#[bench]
+fn open_piece_tree(b: &mut Bencher) {
+    let text = text();
+    b.iter(|| black_box(make_buffer_from_string(text.clone())));
+}
+
+#[bench]
+fn line_at_line_table(b: &mut Bencher) {
+    let table = LineTable::new(text());
+    let mut line_i = 0;
+    b.iter(|| {
+        line_i = (line_i + 7919) % N_LINES;
+        black_box(table.line_at(line_i))
+    });
+}
+
+#[bench]
+fn line_at_piece_tree(b: &mut Bencher) {
+    let buffer = make_buffer_from_string(text());
+    let mut line_i = 0;
+    b.iter(|| {
+        line_i = (line_i + 7919) % N_LINES;
+        black_box(buffer.line_at(line_i))
+    });
+}
+
+#[bench]
+fn insert_line_line_table(b: &mut Bencher) {
+    let mut table = LineTable::new(text());
+    let cursor = Cursor {
+        x: 10,
+        y: N_LINES / 2,
+    };
+    b.iter(|| table.insert_line_at_cursor(&cursor));
+}
+
+#[bench]
+fn insert_line_piece_tree(b: &mut Bencher) {
+    let mut buffer = make_buffer_from_string(text());
+    let cursor = Cursor {
+        x: 10,
+        y: N_LINES / 2,
+    };
+    b.iter(|| buffer.insert_line_at_cursor(&cursor));
+}
+
+#[bench]
+fn typing_line_table(b: &mut Bencher) {
+    let mut table = LineTable::new(text());
+    let mut cursor = Cursor {
+        x: 10,
+        y: N_LINES / 2,
+    };
+    b.iter(|| {
+        table.insert_at_cursor('a', &cursor);
+        cursor.x += 1;
+    });
+}
+
+#[bench]
+fn typing_piece_tree(b: &mut Bencher) {
+    let mut buffer = make_buffer_from_string(text());
+    let mut cursor = Cursor {
+        x: 10,
+        y: N_LINES / 2,
+    };
+    b.iter(|| {
+        buffer.insert_at_cursor('a', &cursor);
+        cursor.x += 1;
+    });
+}
+
+// erases a char in each line in turn
+#[bench]
+fn erase_line_table(b: &mut Bencher) {
+    let mut table = LineTable::new(text());
+    let mut cursor = Cursor { x: 10, y: 0 };
+    b.iter(|| {
+        cursor.y = (cursor.y + 7919) % N_LINES;
+        table.erase_at_cursor(&cursor);
+    });
+}
+
+#[bench]
+fn erase_piece_tree(b: &mut Bencher) {
+    let mut buffer = make_buffer_from_string(text());
+    let mut cursor = Cursor { x: 10, y: 0 };
+    b.iter(|| {
+        cursor.y = (cursor.y + 7919) % N_LINES;
+        buffer.erase_at_cursor(&cursor);
+    });
+}
@@ -0,0 +1,184 @@
+use std::io::{self, Write};
+use std::str;
+
+use encoding_rs::{
+    EncoderResult, Encoding, EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252,
+};
+
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> Option<String> {
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        None
+    } else {
+        Some(text.into_owned())
+    }
+}
+
+// how likely `text` is japanese. half-width katakana are rare,
+// but EUC-JP text often decodes to them as Shift_JIS
+fn japanese_score(text: &str) -> isize {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x3040..=0x30ff | 0x4e00..=0x9fff => 1,
+            0xff61..=0xff9f => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// decodes `bytes` by `encoding`, or by the detected encoding if it is `None`.
+// returns the text, its encoding and whether it began with a BOM,
+// or the encoding `bytes` are not valid in
+pub fn decode(
+    bytes: &[u8],
+    encoding: Option<&'static Encoding>,
+) -> Result<(String, &'static Encoding, bool), &'static Encoding> {
+    if let Some((bom_encoding, bom_length)) = Encoding::for_bom(bytes) {
+        if encoding.map_or(true, |encoding| encoding == bom_encoding) {
+            let text = decode_with(bom_encoding, &bytes[bom_length..]).ok_or(bom_encoding)?;
+            return Ok((text, bom_encoding, true));
+        }
+    }
+    if let Some(encoding) = encoding {
+        let text = decode_with(encoding, bytes).ok_or(encoding)?;
+        return Ok((text, encoding, false));
+    }
+
+    if let Ok(text) = str::from_utf8(bytes) {
+        return Ok((text.to_string(), UTF_8, false));
+    }
+    // latin-1 text is often valid Shift_JIS too, but rarely has japanese chars in it
+    let best = [SHIFT_JIS, EUC_JP]
+        .iter()
+        .filter_map(|encoding| decode_with(encoding, bytes).map(|text| (text, *encoding)))
+        .map(|(text, encoding)| (japanese_score(&text), text, encoding))
+        .filter(|&(score, _, _)| score > 0)
+        .max_by_key(|&(score, _, _)| score);
+    match best {
+        Some((_, text, encoding)) => Ok((text, encoding, false)),
+        // every byte is a char in windows-1252, a superset of latin-1
+        None => decode_with(WINDOWS_1252, bytes)
+            .map(|text| (text, WINDOWS_1252, false))
+            .ok_or(WINDOWS_1252),
+    }
+}
+
+pub fn bom(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_16LE {
+        b"\xff\xfe"
+    } else if encoding == UTF_16BE {
+        b"\xfe\xff"
+    } else if encoding == UTF_8 {
+        b"\xef\xbb\xbf"
+    } else {
+        b""
+    }
+}
+
+fn unencodable(c: char, encoding: &'static Encoding) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("'{}' can not be encoded in {}", c, encoding.name()),
+    )
+}
+
+// encodes UTF-8 written into it. `finish` must be called at the end
+pub struct EncodingWriter<W: Write> {
+    inner: W,
+    encoding: &'static Encoding,
+    encoder: ::encoding_rs::Encoder,
+}
+
+impl<W: Write> EncodingWriter<W> {
+    pub fn new(inner: W, encoding: &'static Encoding) -> Self {
+        EncodingWriter {
+            inner: inner,
+            encoding: encoding,
+            encoder: encoding.new_encoder(),
+        }
+    }
+
+    fn encode(&mut self, text: &str, is_last: bool) -> io::Result<()> {
+        // encoding_rs encodes UTF-16 into UTF-8
+        if self.encoding == UTF_16LE || self.encoding == UTF_16BE {
+            for unit in text.encode_utf16() {
+                let bytes = if self.encoding == UTF_16LE {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                };
+                self.inner.write_all(&bytes)?;
+            }
+            return Ok(());
+        }
+        let length = self
+            .encoder
+            .max_buffer_length_from_utf8_without_replacement(text.len())
+            .unwrap_or(text.len() * 4 + 16);
+        let mut bytes = Vec::with_capacity(length);
+        let (result, _) = self
+            .encoder
+            .encode_from_utf8_to_vec_without_replacement(text, &mut bytes, is_last);
+        match result {
+            EncoderResult::InputEmpty => self.inner.write_all(&bytes),
+            EncoderResult::Unmappable(c) => Err(unencodable(c, self.encoding)),
+            EncoderResult::OutputFull => unreachable!(),
+        }
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.encode("", true)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for EncodingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text =
+            str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.encode(text, false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_latin1() {
+        // "ÉTÉ À la plage, Ça va" in latin-1 is valid Shift_JIS of half-width katakana
+        let bytes = b"\xc9T\xc9 \xc0 la plage, \xc7a va";
+        assert_eq!(
+            decode(bytes, None),
+            Ok(("ÉTÉ À la plage, Ça va".to_string(), WINDOWS_1252, false))
+        );
+    }
+
+    #[test]
+    fn test_decode_japanese() {
+        // "日本語のテキスト"
+        let shift_jis = b"\x93\xfa\x96{\x8c\xea\x82\xcc\x83e\x83L\x83X\x83g";
+        let euc_jp = b"\xc6\xfc\xcb\xdc\xb8\xec\xa4\xce\xa5\xc6\xa5\xad\xa5\xb9\xa5\xc8";
+        let text = "日本語のテキスト".to_string();
+        assert_eq!(
+            decode(shift_jis, None),
+            Ok((text.clone(), SHIFT_JIS, false))
+        );
+        assert_eq!(decode(euc_jp, None), Ok((text.clone(), EUC_JP, false)));
+        assert_eq!(
+            decode(text.as_bytes(), None),
+            Ok((text.clone(), UTF_8, false))
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert_eq!(decode(b"\xff\xfe\x00\xd8", None), Err(UTF_16LE));
+        assert_eq!(decode(b"\x82", Some(SHIFT_JIS)), Err(SHIFT_JIS));
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+// line ending of a file. lines are always separated by '\n' in a buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    Unix, // "\n"
+    Dos,  // "\r\n"
+    Mac,  // "\r"
+}
+
+impl FileFormat {
+    // dos if every '\n' follows '\r', mac if there are only '\r', otherwise unix.
+    // so converting the text back gives the same bytes
+    pub fn detect(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let mut n_lf = 0;
+        let mut n_crlf = 0;
+        let mut n_cr = 0;
+        for (i, b) in bytes.iter().enumerate() {
+            match *b {
+                b'\n' => {
+                    n_lf += 1;
+                    if i > 0 && bytes[i - 1] == b'\r' {
+                        n_crlf += 1;
+                    }
+                }
+                b'\r' => n_cr += 1,
+                _ => (),
+            }
+        }
+        if n_lf > 0 && n_lf == n_crlf {
+            FileFormat::Dos
+        } else if n_lf == 0 && n_cr > 0 {
+            FileFormat::Mac
+        } else {
+            FileFormat::Unix
+        }
+    }
+
+    pub fn newline(&self) -> &'static str {
+        match self {
+            FileFormat::Unix => "\n",
+            FileFormat::Dos => "\r\n",
+            FileFormat::Mac => "\r",
+        }
+    }
+
+    // `text` with its line endings replaced by '\n'
+    pub fn normalize(&self, text: String) -> String {
+        match self {
+            FileFormat::Unix => text,
+            FileFormat::Dos => text.replace("\r\n", "\n"),
+            FileFormat::Mac => text.replace('\r', "\n"),
+        }
+    }
+}
+
+impl FromStr for FileFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unix" => Ok(FileFormat::Unix),
+            "dos" => Ok(FileFormat::Dos),
+            "mac" => Ok(FileFormat::Mac),
+            s => Err(format!("invalid fileformat: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileFormat::Unix => write!(f, "unix"),
+            FileFormat::Dos => write!(f, "dos"),
+            FileFormat::Mac => write!(f, "mac"),
+        }
+    }
+}
+
+// writes '\n' as the line ending of a format
+pub struct NewlineWriter<W: Write> {
+    inner: W,
+    newline: &'static str,
+}
+
+impl<W: Write> NewlineWriter<W> {
+    pub fn new(inner: W, format: FileFormat) -> Self {
+        NewlineWriter {
+            inner: inner,
+            newline: format.newline(),
+        }
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write> Write for NewlineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (i, line) in buf.split(|b| *b == b'\n').enumerate() {
+            if i > 0 {
+                self.inner.write_all(self.newline.as_bytes())?;
+            }
+            self.inner.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::make_buffer_from_string;
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(FileFormat::detect(""), FileFormat::Unix);
+        assert_eq!(FileFormat::detect("a\nb\n"), FileFormat::Unix);
+        assert_eq!(FileFormat::detect("a\r\nb\r\n"), FileFormat::Dos);
+        assert_eq!(FileFormat::detect("a\rb\r"), FileFormat::Mac);
+        // mixed endings and stray '\r' are kept in the text as they are
+        assert_eq!(FileFormat::detect("a\r\nb\n"), FileFormat::Unix);
+        assert_eq!(FileFormat::detect("a\rb\n"), FileFormat::Unix);
+        assert_eq!(FileFormat::detect("a\r\r\nb\r\n"), FileFormat::Dos);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalize = |text: &str| FileFormat::detect(text).normalize(text.to_string());
+        assert_eq!(normalize("a\nb\n"), "a\nb\n");
+        assert_eq!(normalize("a\r\nb\r\n"), "a\nb\n");
+        assert_eq!(normalize("a\rb\r"), "a\nb\n");
+        assert_eq!(normalize("a\r\nb\n"), "a\r\nb\n");
+        assert_eq!(normalize("a\rb\n"), "a\rb\n");
+        assert_eq!(normalize("a\r\r\nb\r\n"), "a\r\nb\n");
+    }
+
+    #[test]
+    fn test_newline_writer() {
+        let write = |format: FileFormat| {
+            let mut out = NewlineWriter::new(vec![], format);
+            out.write_all(b"a\n").unwrap();
+            out.write_all(b"\nb").unwrap();
+            out.get_mut().clone()
+        };
+        assert_eq!(write(FileFormat::Unix), b"a\n\nb");
+        assert_eq!(write(FileFormat::Dos), b"a\r\n\r\nb");
+        assert_eq!(write(FileFormat::Mac), b"a\r\rb");
+    }
+
+    #[test]
+    fn test_write_back_as_loaded() {
+        let texts = [
+            "",
+            "\n",
+            "a",
+            "a\nb\n",
+            "a\nb",
+            "a\r\nb\r\n",
+            "a\r\nb",
+            "a\rb\r",
+            "a\rb",
+            "a\r\nb\n",
+            "a\rb\n",
+            "a\r\r\nb\r\n",
+            "\r\n\r\n",
+        ];
+        for text in texts.iter() {
+            let buffer = make_buffer_from_string(text.to_string());
+            let mut bytes = vec![];
+            buffer.write_contents(&mut bytes).unwrap();
+            assert_eq!(String::from_utf8(bytes).unwrap(), *text);
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::cmp::min;
+use std::io::{self, Write};
+use std::str;
+
+use encoding_rs::Encoding;
+
+// bytes shown in a row of the hex view
+pub const BYTES_PER_ROW: usize = 16;
+// as git does, a file is binary if this head of it contains NUL
+const DETECTION_BYTES: usize = 8000;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let head = &bytes[..min(bytes.len(), DETECTION_BYTES)];
+    // UTF-16 text has NUL in every ASCII char
+    Encoding::for_bom(head).is_none() && head.contains(&0)
+}
+
+// every byte becomes the char of the same code point,
+// so char positions in the buffer are byte offsets in the file
+pub fn decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+// writes chars made by `decode` back as the bytes they came from
+pub struct ByteWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ByteWriter<W> {
+    pub fn new(inner: W) -> Self {
+        ByteWriter { inner: inner }
+    }
+}
+
+impl<W: Write> Write for ByteWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text =
+            str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let bytes: Vec<u8> = text.chars().map(|c| c as u32 as u8).collect();
+        self.inner.write_all(&bytes)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,86 @@
+use super::piece_tree::Snapshot;
+use cursor::Cursor;
+
+// the text before (or after, in the redo stack) an edit
+#[derive(Debug, Clone)]
+pub struct UndoStep {
+    pub snapshot: Snapshot,
+    pub cursor: Cursor,
+}
+
+#[derive(Debug, Clone)]
+struct Group {
+    cursor: Cursor,
+    is_recorded: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct History {
+    undo_stack: Vec<UndoStep>,
+    redo_stack: Vec<UndoStep>,
+    group: Option<Group>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            undo_stack: vec![],
+            redo_stack: vec![],
+            group: None,
+        }
+    }
+}
+
+impl History {
+    pub fn begin_group(&mut self, cursor: &Cursor) {
+        self.end_group();
+        self.group = Some(Group {
+            cursor: cursor.clone(),
+            is_recorded: false,
+        });
+    }
+
+    pub fn end_group(&mut self) {
+        self.group = None;
+    }
+
+    // `before` ... the text before the edit. only the first edit of a group is kept
+    pub fn record(&mut self, before: Snapshot, cursor: &Cursor) {
+        self.redo_stack.clear();
+        if let Some(ref mut group) = self.group {
+            if !group.is_recorded {
+                group.is_recorded = true;
+                self.undo_stack.push(UndoStep {
+                    snapshot: before,
+                    cursor: group.cursor.clone(),
+                });
+            }
+        } else {
+            self.undo_stack.push(UndoStep {
+                snapshot: before,
+                cursor: cursor.clone(),
+            });
+        }
+    }
+
+    // `current` ... the text to go back to by redo
+    pub fn pop_undo(&mut self, current: Snapshot) -> Option<UndoStep> {
+        self.end_group();
+        let step = self.undo_stack.pop()?;
+        self.redo_stack.push(UndoStep {
+            snapshot: current,
+            cursor: step.cursor.clone(),
+        });
+        Some(step)
+    }
+
+    pub fn pop_redo(&mut self, current: Snapshot) -> Option<UndoStep> {
+        self.end_group();
+        let step = self.redo_stack.pop()?;
+        self.undo_stack.push(UndoStep {
+            snapshot: current,
+            cursor: step.cursor.clone(),
+        });
+        Some(step)
+    }
+}
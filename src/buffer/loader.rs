@@ -0,0 +1,62 @@
+use std::str;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use memmap::Mmap;
+
+use super::piece_tree::{is_char_boundary, Summary, MAX_LEAF_BYTES};
+
+// pieces are sent in batches of this many bytes
+const BATCH_BYTES: usize = 1024 * 1024;
+
+pub enum Loaded {
+    // (start, summary) of pieces of the file, in order
+    Pieces(Vec<(usize, Summary)>),
+    Done,
+    // the rest of the file is not valid UTF-8
+    Invalid,
+}
+
+// scans `mmap` in a background thread, cutting it into pieces
+pub fn spawn(mmap: Arc<Mmap>) -> Receiver<Loaded> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let bytes: &[u8] = &mmap;
+        // the newline at the end of the file does not begin a new line
+        let end = if bytes.last() == Some(&b'\n') {
+            bytes.len() - 1
+        } else {
+            bytes.len()
+        };
+        let mut pieces = vec![];
+        let mut batch_start = 0;
+        let mut pos = 0;
+        while pos < end {
+            let mut next = ::std::cmp::min(pos + MAX_LEAF_BYTES, end);
+            while !is_char_boundary(bytes, next) && next > pos + 1 {
+                next -= 1;
+            }
+            let text = match str::from_utf8(&bytes[pos..next]) {
+                Ok(text) => text,
+                Err(_) => {
+                    let _ = sender.send(Loaded::Pieces(pieces));
+                    let _ = sender.send(Loaded::Invalid);
+                    return;
+                }
+            };
+            pieces.push((pos, Summary::of(text)));
+            pos = next;
+            if pos - batch_start >= BATCH_BYTES {
+                batch_start = pos;
+                let batch = ::std::mem::replace(&mut pieces, vec![]);
+                if sender.send(Loaded::Pieces(batch)).is_err() {
+                    return;
+                }
+            }
+        }
+        let _ = sender.send(Loaded::Pieces(pieces));
+        let _ = sender.send(Loaded::Done);
+    });
+    receiver
+}
@@ -0,0 +1,701 @@
+use std::cmp::{max, min};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str;
+use std::sync::Arc;
+
+use memmap::Mmap;
+
+// leaves are cut into chunks of at most this many bytes,
+// so that scanning inside a leaf stays cheap
+pub const MAX_LEAF_BYTES: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Original,
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    bytes: usize,
+    chars: usize,
+    newlines: usize,
+}
+
+impl Summary {
+    pub fn of(text: &str) -> Self {
+        Summary {
+            bytes: text.len(),
+            chars: text.chars().count(),
+            newlines: text.bytes().filter(|b| *b == b'\n').count(),
+        }
+    }
+
+    fn join(&self, other: &Summary) -> Self {
+        Summary {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            newlines: self.newlines + other.newlines,
+        }
+    }
+}
+
+// `summary.bytes` bytes from `start` of `source`
+#[derive(Debug, Clone, Copy)]
+struct Piece {
+    source: Source,
+    start: usize,
+    summary: Summary,
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf(Piece),
+    Branch {
+        left: Rc<Node>,
+        right: Rc<Node>,
+        summary: Summary,
+        height: usize,
+    },
+}
+
+impl Node {
+    fn summary(&self) -> Summary {
+        match self {
+            Node::Leaf(piece) => piece.summary,
+            Node::Branch { summary, .. } => *summary,
+        }
+    }
+
+    fn height(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Branch { height, .. } => *height,
+        }
+    }
+
+    fn children(&self) -> (Rc<Node>, Rc<Node>) {
+        match self {
+            Node::Leaf(_) => unreachable!(),
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => (left.clone(), right.clone()),
+        }
+    }
+}
+
+pub fn is_char_boundary(bytes: &[u8], pos: usize) -> bool {
+    pos >= bytes.len() || bytes[pos] & 0xc0 != 0x80
+}
+
+fn leaf(piece: Piece) -> Rc<Node> {
+    Rc::new(Node::Leaf(piece))
+}
+
+fn branch(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    Rc::new(Node::Branch {
+        summary: left.summary().join(&right.summary()),
+        height: max(left.height(), right.height()) + 1,
+        left: left,
+        right: right,
+    })
+}
+
+// `branch` rotating once (or twice) if heights of `left` and `right` differ by 2
+fn balance(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.height() > right.height() + 1 {
+        let (ll, lr) = left.children();
+        if ll.height() >= lr.height() {
+            branch(ll, branch(lr, right))
+        } else {
+            let (lrl, lrr) = lr.children();
+            branch(branch(ll, lrl), branch(lrr, right))
+        }
+    } else if right.height() > left.height() + 1 {
+        let (rl, rr) = right.children();
+        if rr.height() >= rl.height() {
+            branch(branch(left, rl), rr)
+        } else {
+            let (rll, rlr) = rl.children();
+            branch(branch(left, rll), branch(rlr, rr))
+        }
+    } else {
+        branch(left, right)
+    }
+}
+
+fn join(left: Option<Rc<Node>>, right: Option<Rc<Node>>) -> Option<Rc<Node>> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(left), Some(right)) => Some(join_nodes(left, right)),
+    }
+}
+
+fn join_nodes(left: Rc<Node>, right: Rc<Node>) -> Rc<Node> {
+    if left.height() > right.height() + 1 {
+        let (ll, lr) = left.children();
+        balance(ll, join_nodes(lr, right))
+    } else if right.height() > left.height() + 1 {
+        let (rl, rr) = right.children();
+        balance(join_nodes(left, rl), rr)
+    } else {
+        branch(left, right)
+    }
+}
+
+// a balanced tree built from `leaves` in order
+fn build(leaves: &[Rc<Node>]) -> Option<Rc<Node>> {
+    match leaves.len() {
+        0 => None,
+        1 => Some(leaves[0].clone()),
+        n => {
+            let (left, right) = leaves.split_at(n / 2);
+            join(build(left), build(right))
+        }
+    }
+}
+
+// the text a buffer is opened with
+#[derive(Debug)]
+enum Original {
+    Text(String),
+    // the file must not be changed by others while it is mapped
+    Mapped(Arc<Mmap>),
+}
+
+impl Original {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Original::Text(text) => text.as_bytes(),
+            Original::Mapped(mmap) => &mmap[..],
+        }
+    }
+}
+
+// the text of a buffer as a balanced tree of pieces of the original text and of
+// an append-only buffer. every node caches the numbers of bytes, chars and newlines
+// below it, so that lookups by char or line and edits are logarithmic
+#[derive(Debug, Clone)]
+pub struct PieceTree {
+    original: Rc<Original>,
+    add: String,
+    root: Option<Rc<Node>>,
+    // leaves of a mapped original appended while it is being scanned
+    loaded: Vec<Rc<Node>>,
+}
+
+// a version of the tree, restored in O(1) since nodes are shared between versions.
+// the number of loaded leaves is kept to append those loaded after it was taken
+#[derive(Debug, Clone)]
+pub struct Snapshot(Option<Rc<Node>>, usize);
+
+impl PieceTree {
+    pub fn new(original: String) -> Self {
+        let mut tree = PieceTree {
+            original: Rc::new(Original::Text(original)),
+            add: String::new(),
+            root: None,
+            loaded: vec![],
+        };
+        let leaves = tree.leaves(Source::Original, 0, tree.original.as_bytes().len());
+        tree.root = build(&leaves);
+        tree
+    }
+
+    // empty until pieces of `mmap` are appended by `append_loaded`
+    pub fn mapped(mmap: Arc<Mmap>) -> Self {
+        PieceTree {
+            original: Rc::new(Original::Mapped(mmap)),
+            add: String::new(),
+            root: None,
+            loaded: vec![],
+        }
+    }
+
+    // `pieces` ... (start, summary) of valid UTF-8 in the mapped original
+    pub fn append_loaded(&mut self, pieces: Vec<(usize, Summary)>) {
+        let leaves: Vec<Rc<Node>> = pieces
+            .into_iter()
+            .map(|(start, summary)| {
+                leaf(Piece {
+                    source: Source::Original,
+                    start: start,
+                    summary: summary,
+                })
+            })
+            .collect();
+        let root = self.root.take();
+        self.root = join(root, build(&leaves));
+        self.loaded.extend(leaves);
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        match *self.original {
+            Original::Mapped(_) => true,
+            Original::Text(_) => false,
+        }
+    }
+
+    fn source(&self, source: Source) -> &[u8] {
+        match source {
+            Source::Original => self.original.as_bytes(),
+            Source::Add => self.add.as_bytes(),
+        }
+    }
+
+    fn text(&self, piece: &Piece) -> &str {
+        let start = piece.start;
+        let bytes = &self.source(piece.source)[start..start + piece.summary.bytes];
+        // pieces are valid UTF-8, checked when they are made
+        unsafe { str::from_utf8_unchecked(bytes) }
+    }
+
+    // leaves of the bytes `start..end` of `source`, which must be valid UTF-8
+    fn leaves(&self, source: Source, start: usize, end: usize) -> Vec<Rc<Node>> {
+        let bytes = self.source(source);
+        let mut result = vec![];
+        let mut pos = start;
+        while pos < end {
+            let mut next = min(pos + MAX_LEAF_BYTES, end);
+            while !is_char_boundary(bytes, next) {
+                next -= 1;
+            }
+            let text = unsafe { str::from_utf8_unchecked(&bytes[pos..next]) };
+            result.push(leaf(Piece {
+                source: source,
+                start: pos,
+                summary: Summary::of(text),
+            }));
+            pos = next;
+        }
+        result
+    }
+
+    fn summary(&self) -> Summary {
+        self.root
+            .as_ref()
+            .map(|root| root.summary())
+            .unwrap_or_default()
+    }
+
+    pub fn chars(&self) -> usize {
+        self.summary().chars
+    }
+
+    pub fn lines(&self) -> usize {
+        self.summary().newlines + 1
+    }
+
+    // char position where the line `line_i` begins
+    pub fn line_start(&self, line_i: usize) -> usize {
+        if line_i == 0 {
+            return 0;
+        }
+        if line_i >= self.lines() {
+            return self.chars();
+        }
+        let mut node = self.root.as_ref().unwrap();
+        let mut newlines = line_i;
+        let mut offset = 0;
+        loop {
+            match **node {
+                Node::Leaf(ref piece) => {
+                    let text = self.text(piece);
+                    let (byte, _) = text
+                        .bytes()
+                        .enumerate()
+                        .filter(|&(_, b)| b == b'\n')
+                        .nth(newlines - 1)
+                        .unwrap();
+                    return offset + text[..byte + 1].chars().count();
+                }
+                Node::Branch {
+                    ref left,
+                    ref right,
+                    ..
+                } => {
+                    let left_summary = left.summary();
+                    if newlines <= left_summary.newlines {
+                        node = left;
+                    } else {
+                        newlines -= left_summary.newlines;
+                        offset += left_summary.chars;
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+
+    // chars in `start..end`
+    pub fn slice(&self, start: usize, end: usize) -> String {
+        let mut result = String::new();
+        if let Some(ref root) = self.root {
+            self.slice_impl(root, 0, start, end, &mut result);
+        }
+        result
+    }
+
+    fn slice_impl(&self, node: &Node, offset: usize, start: usize, end: usize, out: &mut String) {
+        let chars = node.summary().chars;
+        if end <= offset || offset + chars <= start {
+            return;
+        }
+        match node {
+            Node::Leaf(piece) => {
+                let from = start.saturating_sub(offset);
+                let to = min(end - offset, chars);
+                out.extend(self.text(piece).chars().skip(from).take(to - from));
+            }
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => {
+                let left_chars = left.summary().chars;
+                self.slice_impl(left, offset, start, end, out);
+                self.slice_impl(right, offset + left_chars, start, end, out);
+            }
+        }
+    }
+
+    fn split(&self, node: Option<Rc<Node>>, pos: usize) -> (Option<Rc<Node>>, Option<Rc<Node>>) {
+        let node = match node {
+            Some(node) => node,
+            None => return (None, None),
+        };
+        let chars = node.summary().chars;
+        if pos == 0 {
+            return (None, Some(node));
+        }
+        if pos >= chars {
+            return (Some(node), None);
+        }
+        match *node {
+            Node::Leaf(ref piece) => {
+                let text = self.text(piece);
+                let byte = text.char_indices().nth(pos).unwrap().0;
+                let left = Piece {
+                    source: piece.source,
+                    start: piece.start,
+                    summary: Summary::of(&text[..byte]),
+                };
+                let right = Piece {
+                    source: piece.source,
+                    start: piece.start + byte,
+                    summary: Summary::of(&text[byte..]),
+                };
+                (Some(leaf(left)), Some(leaf(right)))
+            }
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => {
+                let left_chars = left.summary().chars;
+                if pos < left_chars {
+                    let (a, b) = self.split(Some(left.clone()), pos);
+                    (a, join(b, Some(right.clone())))
+                } else {
+                    let (a, b) = self.split(Some(right.clone()), pos - left_chars);
+                    (join(Some(left.clone()), a), b)
+                }
+            }
+        }
+    }
+
+    // the last leaf of `node` and the rest
+    fn pop_last(node: Rc<Node>) -> (Option<Rc<Node>>, Piece) {
+        match *node {
+            Node::Leaf(piece) => (None, piece),
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => {
+                let (rest, piece) = PieceTree::pop_last(right.clone());
+                (join(Some(left.clone()), rest), piece)
+            }
+        }
+    }
+
+    pub fn insert(&mut self, pos: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let root = self.root.take();
+        let (mut left, right) = self.split(root, pos);
+
+        // typing extends the last piece instead of adding a piece per char
+        let add_start = self.add.len();
+        if let Some(node) = left.take() {
+            let (rest, piece) = PieceTree::pop_last(node);
+            let is_extendable = piece.source == Source::Add
+                && piece.start + piece.summary.bytes == add_start
+                && piece.summary.bytes + text.len() <= MAX_LEAF_BYTES;
+            self.add.push_str(text);
+            if is_extendable {
+                let extended = Piece {
+                    source: Source::Add,
+                    start: piece.start,
+                    summary: piece.summary.join(&Summary::of(text)),
+                };
+                left = join(rest, Some(leaf(extended)));
+                self.root = join(left, right);
+                return;
+            }
+            left = join(rest, Some(leaf(piece)));
+        } else {
+            self.add.push_str(text);
+        }
+
+        let leaves = self.leaves(Source::Add, add_start, self.add.len());
+        self.root = join(join(left, build(&leaves)), right);
+    }
+
+    // removes chars in `start..end`
+    pub fn remove(&mut self, start: usize, end: usize) {
+        if end <= start {
+            return;
+        }
+        let root = self.root.take();
+        let (left, rest) = self.split(root, start);
+        let (_, right) = self.split(rest, end - start);
+        self.root = join(left, right);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(self.root.clone(), self.loaded.len())
+    }
+
+    // whether the text is the version of `snapshot`, as nodes are shared between versions
+    pub fn is_at(&self, snapshot: &Snapshot) -> bool {
+        match (&self.root, &snapshot.0) {
+            (Some(root), Some(other)) => Rc::ptr_eq(root, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        let Snapshot(root, n_loaded) = snapshot;
+        self.root = join(root, build(&self.loaded[n_loaded..]));
+    }
+
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        match self.root {
+            Some(ref root) => self.write_impl(root, out),
+            None => Ok(()),
+        }
+    }
+
+    fn write_impl(&self, node: &Node, out: &mut impl Write) -> io::Result<()> {
+        match node {
+            Node::Leaf(piece) => out.write_all(self.text(piece).as_bytes()),
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => {
+                self.write_impl(left, out)?;
+                self.write_impl(right, out)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `text` of `n` chars mixing 1 to 3 bytes chars and newlines,
+    // so that leaves are cut inside lines and next to multi-byte chars
+    fn text(n: usize) -> String {
+        "ab\nあいc\nd💡e\n".chars().cycle().take(n).collect()
+    }
+
+    fn text_of(tree: &PieceTree) -> String {
+        let mut out = vec![];
+        tree.write_to(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    // checks that `node` is AVL balanced with correct summaries, returning its height
+    fn check_node(tree: &PieceTree, node: &Node) -> usize {
+        match node {
+            Node::Leaf(piece) => {
+                let summary = Summary::of(tree.text(piece));
+                assert_eq!(summary.bytes, piece.summary.bytes);
+                assert_eq!(summary.chars, piece.summary.chars);
+                assert_eq!(summary.newlines, piece.summary.newlines);
+                1
+            }
+            Node::Branch {
+                ref left,
+                ref right,
+                summary,
+                height,
+            } => {
+                let (l, r) = (check_node(tree, left), check_node(tree, right));
+                assert!(max(l, r) - min(l, r) <= 1);
+                assert_eq!(*height, max(l, r) + 1);
+                let joined = left.summary().join(&right.summary());
+                assert_eq!(summary.chars, joined.chars);
+                assert_eq!(summary.newlines, joined.newlines);
+                *height
+            }
+        }
+    }
+
+    // compares every query of `tree` with `model`
+    fn check(tree: &PieceTree, model: &str) {
+        if let Some(ref root) = tree.root {
+            check_node(tree, root);
+        }
+        let chars: Vec<char> = model.chars().collect();
+        assert_eq!(text_of(tree), model);
+        assert_eq!(tree.chars(), chars.len());
+        assert_eq!(tree.lines(), model.matches('\n').count() + 1);
+        let mut line_start = 0;
+        for (line_i, line) in model.split('\n').enumerate() {
+            assert_eq!(tree.line_start(line_i), line_start);
+            line_start += line.chars().count() + 1;
+        }
+        for &(start, end) in &[(0, chars.len()), (1, 3), (chars.len() / 2, chars.len())] {
+            let end = min(end, chars.len());
+            let start = min(start, end);
+            let expected: String = chars[start..end].iter().collect();
+            assert_eq!(tree.slice(start, end), expected);
+        }
+    }
+
+    fn count_leaves(node: &Node) -> usize {
+        match node {
+            Node::Leaf(_) => 1,
+            Node::Branch {
+                ref left,
+                ref right,
+                ..
+            } => count_leaves(left) + count_leaves(right),
+        }
+    }
+
+    fn insert_model(model: &mut String, pos: usize, text: &str) {
+        let byte = model
+            .char_indices()
+            .nth(pos)
+            .map_or(model.len(), |(i, _)| i);
+        model.insert_str(byte, text);
+    }
+
+    fn remove_model(model: &mut String, start: usize, end: usize) {
+        let chars: Vec<char> = model.chars().collect();
+        *model = chars[..start].iter().chain(chars[end..].iter()).collect();
+    }
+
+    #[test]
+    fn test_new() {
+        for n in &[0, 1, 700, 5000] {
+            let text = text(*n);
+            check(&PieceTree::new(text.clone()), &text);
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove_across_leaves() {
+        let mut model = text(3000);
+        let mut tree = PieceTree::new(model.clone());
+        assert!(tree.root.as_ref().unwrap().height() > 1);
+
+        // around the boundaries of leaves cut at `MAX_LEAF_BYTES`
+        for &pos in &[0, 1, 340, 341, 342, 700, 1500, 3000] {
+            let pos = min(pos, model.chars().count());
+            tree.insert(pos, "xあ\n");
+            insert_model(&mut model, pos, "xあ\n");
+            check(&tree, &model);
+        }
+        for &(start, end) in &[(0, 1), (330, 360), (100, 2000), (5, 6)] {
+            tree.remove(start, end);
+            remove_model(&mut model, start, end);
+            check(&tree, &model);
+        }
+        tree.remove(0, tree.chars());
+        check(&tree, "");
+        tree.insert(0, "💡\n");
+        check(&tree, "💡\n");
+    }
+
+    #[test]
+    fn test_typing_extends_a_piece() {
+        let mut model = text(100);
+        let mut tree = PieceTree::new(model.clone());
+        for i in 0..2000 {
+            let c = if i % 10 == 9 { "\n" } else { "い" };
+            tree.insert(50 + i, c);
+            insert_model(&mut model, 50 + i, c);
+        }
+        check(&tree, &model);
+        // 2 original pieces around about 6KB typed in pieces of `MAX_LEAF_BYTES`
+        assert!(count_leaves(tree.root.as_ref().unwrap()) <= 2 + 6000 / MAX_LEAF_BYTES + 2);
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let model = text(2000);
+        let mut tree = PieceTree::new(model.clone());
+        let before = tree.snapshot();
+        tree.insert(10, "abc");
+        tree.remove(500, 900);
+        let edited = text_of(&tree);
+        let after = tree.snapshot();
+
+        tree.restore(before);
+        check(&tree, &model);
+        tree.restore(after);
+        check(&tree, &edited);
+    }
+
+    // random edits, as xorshift keeps them the same in every run
+    #[test]
+    fn test_random_edits() {
+        let mut seed: u32 = 2463534242;
+        let mut random = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed as usize % max(n, 1)
+        };
+        let mut model = text(1500);
+        let mut tree = PieceTree::new(model.clone());
+        let mut snapshots = vec![];
+        for _ in 0..2000 {
+            let chars = model.chars().count();
+            match random(5) {
+                0 | 1 => {
+                    let pos = random(chars + 1);
+                    let text = text(random(40));
+                    tree.insert(pos, &text);
+                    insert_model(&mut model, pos, &text);
+                }
+                2 => {
+                    let start = random(chars + 1);
+                    let end = min(start + random(60), chars);
+                    tree.remove(start, end);
+                    remove_model(&mut model, start, end);
+                }
+                3 => snapshots.push((tree.snapshot(), model.clone())),
+                _ => {
+                    if let Some((snapshot, text)) = snapshots.pop() {
+                        tree.restore(snapshot);
+                        model = text;
+                    }
+                }
+            }
+            check(&tree, &model);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use config::{self, clipboard::Clipboard};
+
+fn spawn(command: &[String], stdout: Stdio) -> Result<::std::process::Child, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "empty clipboard command".to_string())?;
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("can not run {}: {}", program, e))
+}
+
+fn command_of(f: &dyn Fn(&Clipboard) -> Option<Vec<String>>) -> Option<Vec<String>> {
+    let mut command = None;
+    config::clipboard(&mut |clipboard| command = f(clipboard));
+    command
+}
+
+// copies `text` by the copy command in config. returns false if there is no command,
+// in which case the terminal is asked to copy it
+pub fn copy(text: &str) -> Result<bool, String> {
+    let command = match command_of(&|clipboard| clipboard.copy.clone()) {
+        Some(command) => command,
+        None => return Ok(false),
+    };
+    // the output is not read, since commands like `xclip` keep running to serve it
+    let mut child = spawn(&command, Stdio::null())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("can not copy: {}", e))?;
+    }
+    let status = child.wait().map_err(|e| format!("can not copy: {}", e))?;
+    if status.success() {
+        Ok(true)
+    } else {
+        Err(format!("{} failed", command[0]))
+    }
+}
+
+// the text in the clipboard, given by the paste command in config
+pub fn paste() -> Result<String, String> {
+    let command = command_of(&|clipboard| clipboard.paste.clone())
+        .ok_or_else(|| "no paste command in config".to_string())?;
+    let output = spawn(&command, Stdio::piped())?
+        .wait_with_output()
+        .map_err(|e| format!("can not paste: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("{} failed", command[0]));
+    }
+    String::from_utf8(output.stdout).map_err(|_| format!("{} gave invalid UTF-8", command[0]))
+}
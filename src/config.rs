@@ -4,14 +4,31 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
+use error::{Error, Result};
+
+pub mod clipboard;
+pub mod search_highlight;
 pub mod syntax_highlight;
 
+use self::clipboard::Clipboard;
+use self::search_highlight::SearchHighlight;
 use self::syntax_highlight::SyntaxHighlight;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Config {
     pub visible_line_number: bool,
+    #[serde(default)]
+    pub wrap: bool,
+    #[serde(default)]
+    pub scroll_margin: usize,
+    // spaces added by `>` and removed by `<`
+    #[serde(default = "default_shift_width")]
+    pub shift_width: usize,
     pub syntax_highlight: SyntaxHighlight,
+    #[serde(default)]
+    pub search_highlight: SearchHighlight,
+    #[serde(default)]
+    pub clipboard: Clipboard,
 }
 
 use std::sync::Mutex;
@@ -24,24 +41,54 @@ pub fn syntax_highlight(f: &mut impl FnMut(&SyntaxHighlight) -> ()) {
     f(&CONFIG.lock().unwrap().syntax_highlight)
 }
 
+pub fn wrap() -> bool {
+    CONFIG.lock().unwrap().wrap
+}
+
+pub fn scroll_margin() -> usize {
+    CONFIG.lock().unwrap().scroll_margin
+}
+
+fn default_shift_width() -> usize {
+    4
+}
+
+pub fn shift_width() -> usize {
+    CONFIG.lock().unwrap().shift_width
+}
+
+pub fn search_highlight(f: &mut impl FnMut(&SearchHighlight) -> ()) {
+    f(&CONFIG.lock().unwrap().search_highlight)
+}
+
+pub fn clipboard(f: &mut impl FnMut(&Clipboard) -> ()) {
+    f(&CONFIG.lock().unwrap().clipboard)
+}
+
 impl Config {
     pub fn load() -> Self {
         let mut pathbuf = dirs::home_dir().expect("internal error: can not find home directory.");
         pathbuf.push(".config/ysd/config.toml");
-        Self::from_file(pathbuf.as_path()).unwrap_or_else(|e| {
-            eprintln!("invalid config file: {}", e);
-            Config::default()
-        })
+        match Self::from_file(pathbuf.as_path()) {
+            Ok(config) => config,
+            Err(Error::NotFound(_)) => Config::default(),
+            Err(e) => {
+                eprintln!("{}", e);
+                Config::default()
+            }
+        }
     }
 
-    // TODO: use better error type, not String
-    pub fn from_file(filename: &Path) -> Result<Self, String> {
+    pub fn from_file(filename: &Path) -> Result<Self> {
+        let path = filename.to_string_lossy();
         let mut config_file = String::new();
         File::open(filename)
-            .and_then(|mut f| f.read_to_string(&mut config_file))
-            .expect("can not open file");
+            .map_err(|e| Error::open(&path, e))?
+            .read_to_string(&mut config_file)
+            .map_err(|e| Error::read(&path, e))?;
 
-        toml::from_str(config_file.as_str()).map_err(|e| format!("{}", e))
+        toml::from_str(config_file.as_str())
+            .map_err(|e| Error::InvalidConfig(path.to_string(), format!("{}", e)))
     }
 }
 
@@ -49,7 +96,12 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             visible_line_number: false,
+            wrap: false,
+            scroll_margin: 0,
+            shift_width: default_shift_width(),
             syntax_highlight: SyntaxHighlight::default(),
+            search_highlight: SearchHighlight::default(),
+            clipboard: Clipboard::default(),
         }
     }
 }
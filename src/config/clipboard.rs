@@ -0,0 +1,21 @@
+use serde_derive::{Deserialize, Serialize};
+
+// commands to access the clipboard of the system, e.g. `["xclip", "-selection", "clipboard"]`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Clipboard {
+    // the text is given to its stdin. without it, the terminal copies by OSC 52
+    #[serde(default)]
+    pub copy: Option<Vec<String>>,
+    // the text is read from its stdout
+    #[serde(default)]
+    pub paste: Option<Vec<String>>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Clipboard {
+            copy: None,
+            paste: None,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use serde_derive::{Deserialize, Serialize};
+
+use util::Rgb;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SearchHighlight {
+    pub color: Rgb,
+}
+
+impl Default for SearchHighlight {
+    fn default() -> Self {
+        SearchHighlight {
+            color: Rgb(120, 100, 0),
+        }
+    }
+}
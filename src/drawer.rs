@@ -1,119 +1,435 @@
+use std::cmp::{max, min};
 use std::io::{stdout, Stdout, Write};
 
+use regex::Regex;
 use termion::clear;
 use termion::color;
 use termion::cursor::{Goto, Restore, Save};
 use termion::input::MouseTerminal;
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::AlternateScreen;
+use termion::style;
 
-use buffer::Buffer;
-use config::syntax_highlight;
+use buffer::{Buffer, BYTES_PER_ROW};
+use config::{search_highlight, syntax_highlight};
 use cursor::Cursor;
 use frame::Frame;
 use layout::Layout;
+use operator::Range;
+use search;
 use state::State;
-use util::{clamp, Direction, Rgb};
+use util::{self, char_display, clamp, Direction, Rgb};
+use viewport::{self, Viewport};
 
 pub struct Drawer {
     out: MouseTerminal<AlternateScreen<RawTerminal<Stdout>>>,
 }
 
-fn draw_plain_buffer(out: &mut impl Write, buffer: &Buffer, cursor: &Cursor, frame: &Frame) {
-    // `+1` means convertion from 0-origin position to 1-origin position
-    let frame_x = frame.x + 1;
-    let frame_y = frame.y + 1;
+// matches of the search pattern and their background color
+struct SearchMatches {
+    regex: Regex,
+    color: Rgb,
+}
 
-    let top_line = if buffer.height() < frame.height || cursor.y < frame.height / 2 {
-        0
-    } else if cursor.y + frame.height / 2 > buffer.height() {
-        buffer.height() - frame.height
-    } else {
-        cursor.y - frame.height / 2
-    };
-
-    for i in { 0..frame.height } {
-        write!(out, "{}", Goto(frame_x as u16, frame_y as u16 + i as u16)).unwrap();
-        if i + top_line < buffer.height() {
-            let line: String = buffer.line_at(top_line + i).into_iter().collect();
-            write!(out, "{}", line.as_str()).unwrap();
+// which part of a buffer is drawn in a frame
+struct View {
+    top_line: usize,
+    left_column: usize,
+    is_wrapped: bool,
+}
+
+impl View {
+    fn new(viewport: &Viewport, is_wrapped: bool) -> Self {
+        View {
+            top_line: viewport.top_line,
+            left_column: if is_wrapped { 0 } else { viewport.left_column },
+            is_wrapped: is_wrapped,
+        }
+    }
+
+    // rows of the hex view are always drawn from the left end
+    fn hex(viewport: &Viewport) -> Self {
+        View {
+            top_line: viewport.top_line,
+            left_column: 0,
+            is_wrapped: false,
+        }
+    }
+
+    // screen rows taken by the line `line_i`
+    fn rows_of(&self, buffer: &Buffer, line_i: usize, frame_width: usize) -> usize {
+        viewport::rows_of(buffer, line_i, frame_width, self.is_wrapped)
+    }
+
+    // 0-origin position of the cursor in the frame
+    fn cursor_position(&self, buffer: &Buffer, cursor: &Cursor, frame: &Frame) -> (usize, usize) {
+        if buffer.is_binary() {
+            let col = min(hex_column(cursor.x), frame.width.saturating_sub(1));
+            return (col, cursor.y.saturating_sub(self.top_line));
+        }
+        let line = buffer.line_at(cursor.y);
+        let positions = viewport::char_positions(&line, frame.width, self.is_wrapped);
+        let (col, row) = positions[clamp(cursor.x, 0, line.len())];
+        let rows_above: usize = (self.top_line..cursor.y)
+            .map(|line_i| self.rows_of(buffer, line_i, frame.width))
+            .sum();
+        (col.saturating_sub(self.left_column), rows_above + row)
+    }
+}
+
+// prints a line piece by piece, clipping (or wrapping) it to the frame,
+// painting the background of search matches and inverting the selection
+struct LinePrinter {
+    matches: Vec<(usize, usize)>,
+    color: Rgb,
+    // chars selected in Visual Mode, from and up to (excluding)
+    selected: Option<(usize, usize)>,
+    left_column: usize,
+    is_wrapped: bool,
+    frame_x: usize,
+    frame_y: usize,
+    width: usize,
+    max_rows: usize,
+    char_i: usize,
+    col: usize,
+    row: usize,
+    is_painting: bool,
+    is_inverted: bool,
+    is_in_escape: bool,
+}
+
+impl LinePrinter {
+    // `row` ... row of the line in `frame`
+    fn new(
+        line: &str,
+        view: &View,
+        frame: &Frame,
+        row: usize,
+        search_matches: Option<&SearchMatches>,
+        selected: Option<(usize, usize)>,
+    ) -> Self {
+        let (matches, color) = match search_matches {
+            Some(search_matches) => (
+                search::find_in_line(line, &search_matches.regex),
+                search_matches.color,
+            ),
+            None => (vec![], Rgb(0, 0, 0)),
+        };
+        // `+1` means convertion from 0-origin position to 1-origin position
+        LinePrinter {
+            matches: matches,
+            color: color,
+            selected: selected,
+            left_column: view.left_column,
+            is_wrapped: view.is_wrapped,
+            frame_x: frame.x + 1,
+            frame_y: frame.y + row + 1,
+            width: frame.width,
+            max_rows: frame.height.saturating_sub(row),
+            char_i: 0,
+            col: 0,
+            row: 0,
+            is_painting: false,
+            is_inverted: false,
+            is_in_escape: false,
+        }
+    }
+
+    fn print(&mut self, out: &mut impl Write, text: &str) {
+        for c in text.chars() {
+            // escape sequences (e.g. colors in the status bar) take no columns
+            if self.is_in_escape || c == '\x1b' {
+                write!(out, "{}", c).unwrap();
+                self.is_in_escape = !c.is_ascii_alphabetic();
+                continue;
+            }
+
+            // search matches are in chars, the screen is in display width
+            let char_i = self.char_i;
+            self.char_i += 1;
+            let is_wrapped = self.is_wrapped && self.width > 0;
+            let (col, c_width, is_new_row) =
+                viewport::place_char(c, self.col, self.width, is_wrapped);
+            if is_new_row {
+                self.col = 0;
+                self.row += 1;
+                if self.row < self.max_rows {
+                    let goto_y = self.frame_y + self.row;
+                    write!(out, "{}", Goto(self.frame_x as u16, goto_y as u16)).unwrap();
+                }
+            }
+            self.col = col + c_width;
+            if self.width == 0 || self.row >= self.max_rows {
+                continue;
+            }
+            if !self.is_wrapped {
+                let right = self.left_column + self.width;
+                if col + c_width <= self.left_column || right <= col {
+                    continue;
+                }
+                // wide chars cut by the edges of the frame
+                if col < self.left_column || right < col + c_width {
+                    let visible = min(col + c_width, right) - max(col, self.left_column);
+                    write!(out, "{}", " ".repeat(visible)).unwrap();
+                    continue;
+                }
+            }
+
+            let is_matched = self
+                .matches
+                .iter()
+                .any(|(start, end)| *start <= char_i && char_i < *end);
+            if is_matched && !self.is_painting {
+                let Rgb(r, g, b) = self.color;
+                write!(out, "{}", color::Bg(color::Rgb(r, g, b))).unwrap();
+            } else if !is_matched && self.is_painting {
+                write!(out, "{}", color::Bg(color::Reset)).unwrap();
+            }
+            self.is_painting = is_matched;
+            let is_selected = self
+                .selected
+                .map_or(false, |(start, end)| start <= char_i && char_i < end);
+            if is_selected != self.is_inverted {
+                if is_selected {
+                    write!(out, "{}", style::Invert).unwrap();
+                } else {
+                    write!(out, "{}", style::NoInvert).unwrap();
+                }
+                self.is_inverted = is_selected;
+            }
+            // tabs and control characters are never written as they are
+            if c == '\t' {
+                write!(out, "{}", " ".repeat(c_width)).unwrap();
+            } else if c.is_control() {
+                write!(out, "{}", char_display(c, col)).unwrap();
+            } else {
+                write!(out, "{}", c).unwrap();
+            }
         }
     }
 
-    // cursor
-    let x = clamp(cursor.x, 0, buffer.line_at(cursor.y).len()) + frame.x + 1;
-    let y = frame.y + cursor.y - top_line + 1;
+    fn finish(&mut self, out: &mut impl Write) {
+        if self.is_painting {
+            write!(out, "{}", color::Bg(color::Reset)).unwrap();
+            self.is_painting = false;
+        }
+        if self.is_inverted {
+            write!(out, "{}", style::NoInvert).unwrap();
+            self.is_inverted = false;
+        }
+        if self.width == 0 || self.is_wrapped {
+            return;
+        }
+
+        // marks of text continuing off-screen
+        let goto_y = self.frame_y;
+        let mark = |out: &mut dyn Write, x: usize, c: char| {
+            write!(
+                out,
+                "{}{}{}{}",
+                Goto(x as u16, goto_y as u16),
+                style::Invert,
+                c,
+                style::Reset
+            )
+            .unwrap();
+        };
+        if self.left_column > 0 && self.col > 0 {
+            mark(out, self.frame_x, '<');
+        }
+        if self.col > self.left_column + self.width {
+            mark(out, self.frame_x + self.width - 1, '>');
+        }
+    }
+}
 
-    write!(out, "{}", Goto(x as u16, y as u16)).unwrap();
+// line numbers are drawn only on the first row of wrapped lines
+fn draw_line_numbers(
+    out: &mut impl Write,
+    buffer: &Buffer,
+    view: &View,
+    frame: &Frame,
+    text_frame: &Frame,
+) {
+    let width = buffer.height().to_string().len();
+    let mut row = 0;
+    for line_i in view.top_line..buffer.height() {
+        if row >= frame.height {
+            break;
+        }
+        let goto_y = frame.y + row + 1;
+        write!(
+            out,
+            "{} {:width$} ",
+            Goto(frame.x as u16 + 1, goto_y as u16),
+            line_i + 1,
+            width = width
+        )
+        .unwrap();
+        row += view.rows_of(buffer, line_i, text_frame.width);
+    }
 }
 
-fn print_comment_part(out: &mut impl Write, word: String, color: Rgb) {
-    write!(
-        out,
-        "{}{}{}",
-        color::Fg(color::Rgb(color.0, color.1, color.2)),
-        word,
-        color::Fg(color::Reset)
-    )
-    .unwrap();
+fn draw_plain_buffer(
+    out: &mut impl Write,
+    buffer: &Buffer,
+    view: &View,
+    frame: &Frame,
+    search_matches: Option<&SearchMatches>,
+    selection: Option<&Range>,
+) {
+    let mut row = 0;
+    for line_i in view.top_line..buffer.height() {
+        if row >= frame.height {
+            break;
+        }
+        let goto_y = frame.y + row + 1;
+        write!(out, "{}", Goto(frame.x as u16 + 1, goto_y as u16)).unwrap();
+        let line: String = buffer.line_at(line_i).into_iter().collect();
+        let selected = selection.and_then(|range| range.chars_in_line(buffer, line_i));
+        let mut printer = LinePrinter::new(&line, view, frame, row, search_matches, selected);
+        printer.print(out, line.as_str());
+        printer.finish(out);
+        row += view.rows_of(buffer, line_i, frame.width);
+    }
 }
 
-fn print_non_comment_part(out: &mut impl Write, line: String, keyword: &syntax_highlight::Keyword) {
+// column of the hex digits of the `x`-th byte in a row of the hex view, e.g.
+// `00000010  48 65 6c 6c 6f 0a 00 00  00 00 00 00 00 00 00 00  |Hello...........|`
+fn hex_column(x: usize) -> usize {
+    10 + 3 * x + if x >= BYTES_PER_ROW / 2 { 1 } else { 0 }
+}
+
+// each line of a binary buffer is a row of offset, hex bytes and ASCII
+fn draw_hex_buffer(
+    out: &mut impl Write,
+    buffer: &Buffer,
+    view: &View,
+    frame: &Frame,
+    search_matches: Option<&SearchMatches>,
+) {
+    for (row, line_i) in (view.top_line..buffer.height())
+        .take(frame.height)
+        .enumerate()
+    {
+        let goto_y = frame.y + row + 1;
+        write!(out, "{}", Goto(frame.x as u16 + 1, goto_y as u16)).unwrap();
+        let line = buffer.line_at(line_i);
+        let matches = match search_matches {
+            Some(search_matches) => {
+                let text: String = line.iter().collect();
+                search::find_in_line(&text, &search_matches.regex)
+            }
+            None => vec![],
+        };
+        let paint = |i: usize, text: String| match search_matches {
+            Some(search_matches) if matches.iter().any(|(start, end)| *start <= i && i < *end) => {
+                let Rgb(r, g, b) = search_matches.color;
+                format!(
+                    "{}{}{}",
+                    color::Bg(color::Rgb(r, g, b)),
+                    text,
+                    color::Bg(color::Reset)
+                )
+            }
+            _ => text,
+        };
+
+        let mut hex = format!("{:08x} ", line_i * BYTES_PER_ROW);
+        let mut ascii = String::new();
+        for i in 0..BYTES_PER_ROW {
+            if i == BYTES_PER_ROW / 2 {
+                hex.push(' ');
+            }
+            match line.get(i) {
+                Some(c) => {
+                    let byte = *c as u32 as u8;
+                    hex.push(' ');
+                    hex += paint(i, format!("{:02x}", byte)).as_str();
+                    let c = if byte.is_ascii_graphic() || byte == b' ' {
+                        byte as char
+                    } else {
+                        '.'
+                    };
+                    ascii += paint(i, c.to_string()).as_str();
+                }
+                None => hex += "   ",
+            }
+        }
+        let mut printer = LinePrinter::new("", view, frame, row, None, None);
+        printer.print(out, format!("{}  |{}|", hex, ascii).as_str());
+        printer.finish(out);
+    }
+}
+
+fn print_comment_part(out: &mut impl Write, printer: &mut LinePrinter, word: String, color: Rgb) {
+    write!(out, "{}", color::Fg(color::Rgb(color.0, color.1, color.2))).unwrap();
+    printer.print(out, word.as_str());
+    write!(out, "{}", color::Fg(color::Reset)).unwrap();
+}
+
+fn print_word(
+    out: &mut impl Write,
+    printer: &mut LinePrinter,
+    word: String,
+    keyword: &syntax_highlight::Keyword,
+) {
+    if keyword.keywords.contains(&word) {
+        let Rgb(r, g, b) = keyword.color;
+        write!(out, "{}", color::Fg(color::Rgb(r, g, b))).unwrap();
+        printer.print(out, word.as_str());
+        write!(out, "{}", color::Fg(color::Reset)).unwrap();
+    } else {
+        printer.print(out, word.as_str());
+    }
+}
+
+fn print_non_comment_part(
+    out: &mut impl Write,
+    printer: &mut LinePrinter,
+    line: String,
+    keyword: &syntax_highlight::Keyword,
+) {
     let mut word = String::new();
     for c in line.chars() {
         if c.is_alphabetic() || c == '_' {
             word.push(c);
         } else {
-            if keyword.keywords.contains(&word) {
-                let Rgb(r, g, b) = keyword.color;
-                write!(
-                    out,
-                    "{}{}{}",
-                    color::Fg(color::Rgb(r, g, b)),
-                    word,
-                    color::Fg(color::Reset)
-                )
-                .unwrap();
-            } else {
-                write!(out, "{}", word).unwrap();
-            }
+            print_word(out, printer, word, keyword);
             word = String::new();
-            write!(out, "{}", c).unwrap();
+            printer.print(out, c.to_string().as_str());
         }
     }
+    print_word(out, printer, word, keyword);
 }
 
 fn draw_syntax_highlighted_buffer(
     out: &mut impl Write,
     buffer: &Buffer,
-    cursor: &Cursor,
+    view: &View,
     frame: &Frame,
+    search_matches: Option<&SearchMatches>,
+    selection: Option<&Range>,
 ) {
-    // `+1` means convertion from 0-origin position to 1-origin position
-    let frame_x = frame.x + 1;
-    let frame_y = frame.y + 1;
-
-    let top_line = if buffer.height() < frame.height || cursor.y < frame.height / 2 {
-        0
-    } else if cursor.y + frame.height / 2 > buffer.height() {
-        buffer.height() - frame.height
-    } else {
-        cursor.y - frame.height / 2
-    };
-
     syntax_highlight(
         &mut |syntax_highlight: &syntax_highlight::SyntaxHighlight| {
             let ref keyword = syntax_highlight.keyword;
             let ref comment = syntax_highlight.comment;
 
             let mut is_comment = false;
-            for i in { 0..frame.height } {
-                if i + top_line >= buffer.height() {
+            let mut row = 0;
+            for line_i in view.top_line..buffer.height() {
+                if row >= frame.height {
                     break;
                 }
-                write!(out, "{}", Goto(frame_x as u16, frame_y as u16 + i as u16)).unwrap();
+                let goto_y = frame.y + row + 1;
+                write!(out, "{}", Goto(frame.x as u16 + 1, goto_y as u16)).unwrap();
 
-                let mut line: String = buffer.line_at(top_line + i).iter().collect();
+                let mut line: String = buffer.line_at(line_i).iter().collect();
+                let selected = selection.and_then(|range| range.chars_in_line(buffer, line_i));
+                let mut printer =
+                    LinePrinter::new(&line, view, frame, row, search_matches, selected);
+                row += view.rows_of(buffer, line_i, frame.width);
 
                 loop {
                     if is_comment {
@@ -122,7 +438,7 @@ fn draw_syntax_highlighted_buffer(
                                 comment_pos += end_comment_mark.len();
                                 let mut left = line;
                                 let right = left.split_off(comment_pos);
-                                print_comment_part(out, left, comment.color);
+                                print_comment_part(out, &mut printer, left, comment.color);
                                 is_comment = false;
                                 line = right;
                             }
@@ -148,8 +464,8 @@ fn draw_syntax_highlighted_buffer(
                             if line_pos < multi_pos {
                                 let mut left = line;
                                 let right = left.split_off(line_pos);
-                                print_non_comment_part(out, left, keyword);
-                                print_comment_part(out, right, comment.color);
+                                print_non_comment_part(out, &mut printer, left, keyword);
+                                print_comment_part(out, &mut printer, right, comment.color);
                                 line = String::new();
                                 break;
                             } else {
@@ -158,8 +474,8 @@ fn draw_syntax_highlighted_buffer(
                                 let right = mid.split_off(
                                     comment.multi_comment_mark.as_ref().unwrap().0.len(),
                                 );
-                                print_non_comment_part(out, left, keyword);
-                                print_comment_part(out, mid, comment.color);
+                                print_non_comment_part(out, &mut printer, left, keyword);
+                                print_comment_part(out, &mut printer, mid, comment.color);
                                 line = right;
                                 is_comment = true;
                                 continue;
@@ -168,8 +484,8 @@ fn draw_syntax_highlighted_buffer(
                         (Some(line_pos), None) => {
                             let mut left = line;
                             let right = left.split_off(line_pos);
-                            print_non_comment_part(out, left, keyword);
-                            print_comment_part(out, right, comment.color);
+                            print_non_comment_part(out, &mut printer, left, keyword);
+                            print_comment_part(out, &mut printer, right, comment.color);
                             line = String::new();
                             break;
                         }
@@ -178,8 +494,8 @@ fn draw_syntax_highlighted_buffer(
                             let mut mid = left.split_off(multi_pos);
                             let right =
                                 mid.split_off(comment.multi_comment_mark.as_ref().unwrap().0.len());
-                            print_non_comment_part(out, left, keyword);
-                            print_comment_part(out, mid, comment.color);
+                            print_non_comment_part(out, &mut printer, left, keyword);
+                            print_comment_part(out, &mut printer, mid, comment.color);
                             line = right;
                             is_comment = true;
                             continue;
@@ -188,25 +504,33 @@ fn draw_syntax_highlighted_buffer(
                     }
                 }
                 if is_comment {
-                    print_comment_part(out, line, comment.color);
+                    print_comment_part(out, &mut printer, line, comment.color);
                 } else {
-                    print_non_comment_part(out, line, keyword);
+                    print_non_comment_part(out, &mut printer, line, keyword);
                 }
+                printer.finish(out);
             }
         },
     );
-    // cursor
-    let x = clamp(cursor.x, 0, buffer.line_at(cursor.y).len()) + frame.x + 1;
-    let y = frame.y + cursor.y - top_line + 1;
-
-    write!(out, "{}", Goto(x as u16, y as u16)).unwrap();
 }
 
 impl Drawer {
+    // asks the terminal to copy `text` to the clipboard by OSC 52
+    pub fn copy_to_clipboard(&mut self, text: &str) {
+        write!(self.out, "\x1b]52;c;{}\x07", util::base64(text.as_bytes())).unwrap();
+        self.out.flush().unwrap();
+    }
+
     pub fn draw(&mut self, state: &State) {
         write!(self.out, "{}", clear::All).unwrap();
 
-        fn draw_layout(out: &mut impl Write, state: &State, layout: &Layout, frame: &Frame) {
+        fn draw_layout(
+            out: &mut impl Write,
+            state: &State,
+            search_matches: Option<&SearchMatches>,
+            layout: &Layout,
+            frame: &Frame,
+        ) {
             use self::Layout::*;
             match layout {
                 Panel(panel, panel_name) => {
@@ -214,36 +538,97 @@ impl Drawer {
                         format!("internal error: unknown buffer name {}", panel_name).as_str(),
                     );
 
-                    let buffer_frame = if panel.is_visible_line_number {
-                        let line_buf = Buffer::line_number(buf.height());
-                        let frame_width = line_buf.line_width_at(0);
+                    // offsets are shown in place of line numbers of binary buffers
+                    let (line_frame, buffer_frame) = if panel.is_visible_line_number
+                        && !buf.is_binary()
+                    {
+                        let frame_width = Buffer::line_number_width(buf.height());
                         let (line_frame, buffer_frame) = frame.split(&Direction::Left, frame_width);
-                        draw_plain_buffer(out, &line_buf, &panel.cursor, &line_frame);
-                        buffer_frame
+                        (Some(line_frame), buffer_frame)
+                    } else {
+                        (None, frame.clone())
+                    };
+                    let view = if buf.is_binary() {
+                        View::hex(&panel.viewport)
                     } else {
-                        frame.clone()
+                        View::new(&panel.viewport, panel.wraps(buf))
                     };
 
-                    if panel.enable_syntax_highlight {
-                        draw_syntax_highlighted_buffer(out, buf, &panel.cursor, &buffer_frame);
+                    if let Some(line_frame) = line_frame {
+                        draw_line_numbers(out, buf, &view, &line_frame, &buffer_frame);
+                    }
+
+                    let search_matches = if state.status.is_status_buffer(panel.buffer_id) {
+                        None
                     } else {
-                        draw_plain_buffer(out, buf, &panel.cursor, &buffer_frame);
+                        search_matches
+                    };
+                    // the selection is in the current panel
+                    let selection = match state.selection {
+                        Some(ref selection) if panel_name == &state.current_panel_name => {
+                            Some(selection.range(buf, &panel.cursor))
+                        }
+                        _ => None,
+                    };
+                    if buf.is_binary() {
+                        draw_hex_buffer(out, buf, &view, &buffer_frame, search_matches);
+                    } else if panel.enable_syntax_highlight {
+                        draw_syntax_highlighted_buffer(
+                            out,
+                            buf,
+                            &view,
+                            &buffer_frame,
+                            search_matches,
+                            selection.as_ref(),
+                        );
+                    } else {
+                        draw_plain_buffer(
+                            out,
+                            buf,
+                            &view,
+                            &buffer_frame,
+                            search_matches,
+                            selection.as_ref(),
+                        );
                     }
 
                     // save cursor pos
                     if panel_name == &state.current_panel_name {
-                        write!(out, "{}", Save).unwrap();
+                        let (x, y) = view.cursor_position(buf, &panel.cursor, &buffer_frame);
+                        let goto_x = buffer_frame.x + x + 1;
+                        let goto_y = buffer_frame.y + y + 1;
+                        write!(out, "{}{}", Goto(goto_x as u16, goto_y as u16), Save).unwrap();
                     }
                 }
-                Lined(dir, line_width, line, body) => {
-                    let (line_frame, body_frame) = frame.split(dir, *line_width);
-                    draw_layout(out, state, line, &line_frame);
-                    draw_layout(out, state, body, &body_frame);
+                Lined(dir, size, line, body) => {
+                    let (line_frame, body_frame) = frame.split_by(dir, size);
+                    draw_layout(out, state, search_matches, line, &line_frame);
+                    draw_layout(out, state, search_matches, body, &body_frame);
                 }
             }
         }
 
-        draw_layout(&mut self.out, state, &state.layout, &Frame::screen());
+        let search_matches = match state.search {
+            Some(ref search) if state.is_search_highlighted => {
+                search::compile(&search.pattern).ok().map(|regex| {
+                    let mut color = Rgb(0, 0, 0);
+                    search_highlight(&mut |search_highlight| color = search_highlight.color);
+                    SearchMatches {
+                        regex: regex,
+                        color: color,
+                    }
+                })
+            }
+            _ => None,
+        };
+
+        draw_layout(
+            &mut self.out,
+            state,
+            search_matches.as_ref(),
+            &state.layout,
+            &Frame::screen(),
+        );
         write!(self.out, "{}", Restore).unwrap();
         self.out.flush().unwrap();
     }
@@ -253,7 +638,16 @@ impl Default for Drawer {
     fn default() -> Self {
         let out = AlternateScreen::from(stdout().into_raw_mode().unwrap());
         let mut out = MouseTerminal::from(out);
+        // pasted text comes between marks instead of as typed keys
+        write!(out, "\x1b[?2004h").unwrap();
         out.flush().unwrap();
         Drawer { out: out }
     }
 }
+
+impl Drop for Drawer {
+    fn drop(&mut self) {
+        write!(self.out, "\x1b[?2004l").unwrap();
+        self.out.flush().unwrap();
+    }
+}
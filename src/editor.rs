@@ -1,6 +1,8 @@
-use termion::event::Event;
+use encoding_rs::Encoding;
+use termion::event::{Event, Key};
 
 use crate::drawer::Drawer;
+use crate::error::Result;
 use crate::event_worker::{command_worker::CommandWorker, EventWorker};
 use crate::state::State;
 
@@ -11,16 +13,18 @@ pub struct Editor {
 }
 
 impl Editor {
-    pub fn from_file(filename: &str) -> Self {
+    pub fn from_files(filenames: &[String], encoding: Option<&'static Encoding>) -> Result<Self> {
         let event_worker = Box::new(CommandWorker::default());
-        let drawer = Drawer::default();
-        let mut state = State::from_file(filename);
+        let mut state = State::from_files(filenames, encoding)?;
         state.update_mode(event_worker.mode());
-        Editor {
+        state.update_info();
+        // enter the alternate screen only after the files could be opened
+        let drawer = Drawer::default();
+        Ok(Editor {
             event_worker: event_worker,
             state: state,
             drawer: drawer,
-        }
+        })
     }
 
     pub fn update(&mut self, e: Event) {
@@ -29,9 +33,36 @@ impl Editor {
             self.event_worker = next_worker;
         }
         self.state.clamp_cursor();
+        self.state.update_info();
+    }
+
+    // the whole text is one change in Edit Mode, other modes take it as typed keys
+    pub fn paste(&mut self, text: &str) {
+        if self.event_worker.paste(&mut self.state, text) {
+            self.state.clamp_cursor();
+            self.state.update_info();
+        } else {
+            for c in text.chars() {
+                self.update(Event::Key(Key::Char(c)));
+            }
+        }
+    }
+
+    // frames are computed from the screen size on each traversal,
+    // so only cursors need to be fixed for the new size
+    pub fn resize(&mut self) {
+        self.state.clamp_cursor();
+    }
+
+    // returns whether the screen needs to be redrawn
+    pub fn tick(&mut self) -> bool {
+        self.state.poll_loading()
     }
 
     pub fn draw(&mut self) {
+        if let Some(text) = self.state.clipboard_out.take() {
+            self.drawer.copy_to_clipboard(&text);
+        }
         self.drawer.draw(&self.state);
     }
 }
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    NotFound(String),
+    PermissionDenied(String),
+    Open(String, io::Error),
+    Read(String, io::Error),
+    Write(String, io::Error),
+    // path and the name of the encoding
+    Encoding(String, &'static str),
+    InvalidConfig(String, String),
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl Error {
+    pub fn open(path: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(path.to_string()),
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_string()),
+            _ => Error::Open(path.to_string(), err),
+        }
+    }
+
+    pub fn read(path: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidData => Error::Encoding(path.to_string(), "UTF-8"),
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_string()),
+            _ => Error::Read(path.to_string(), err),
+        }
+    }
+
+    pub fn write(path: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied(path.to_string()),
+            _ => Error::Write(path.to_string(), err),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Error::*;
+        match self {
+            NotFound(path) => write!(f, "\"{}\": no such file", path),
+            PermissionDenied(path) => write!(f, "\"{}\": permission denied", path),
+            Open(path, err) => write!(f, "\"{}\": can not open: {}", path, err),
+            Read(path, err) => write!(f, "\"{}\": can not read: {}", path, err),
+            Write(path, err) => write!(f, "\"{}\": can not write: {}", path, err),
+            Encoding(path, encoding) => write!(f, "\"{}\": not a valid {} text", path, encoding),
+            InvalidConfig(path, msg) => write!(f, "\"{}\": invalid config: {}", path, msg),
+        }
+    }
+}
+
+impl ::std::error::Error for Error {}
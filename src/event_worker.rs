@@ -3,8 +3,16 @@ use termion::event::Event;
 
 pub mod command_worker;
 pub mod edit_worker;
+pub mod hex_edit_worker;
+pub mod operator_worker;
+pub mod search_worker;
+pub mod visual_worker;
 
 pub trait EventWorker {
     fn mode(&self) -> String;
     fn update(&mut self, state: &mut State, e: Event) -> Option<Box<dyn EventWorker>>;
+    // returns whether the worker takes pasted text at once
+    fn paste(&mut self, _state: &mut State, _text: &str) -> bool {
+        false
+    }
 }
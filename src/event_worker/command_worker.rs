@@ -1,22 +1,91 @@
 use termion::color::{Bg, Magenta, Reset};
 use termion::event::{Event, Key};
 
-use super::{edit_worker::EditWorker, EventWorker};
-use state::State;
+use super::{
+    edit_worker::EditWorker, hex_edit_worker::HexEditWorker, operator_worker::OperatorWorker,
+    search_worker::SearchWorker, visual_worker::VisualWorker, EventWorker,
+};
+use motion::Motion;
+use operator::Operator;
+use register;
+use search::SearchDirection;
+use selection::VisualKind;
+use state::{ScrollUnit, State};
+use substitute::Substitute;
 use util::Direction;
+use viewport::Alignment;
 
 #[derive(Debug)]
 pub struct CommandWorker {
     input: String,
 }
 
+// splits the count typed before a command, e.g. `3` of `3w`.
+// `0` alone is a command, not a count
+pub fn split_count(input: &str) -> (Option<usize>, &str) {
+    let n_digits = input.chars().take_while(|c| c.is_ascii_digit()).count();
+    if n_digits == 0 || input.starts_with('0') {
+        (None, input)
+    } else {
+        (input[..n_digits].parse().ok(), &input[n_digits..])
+    }
+}
+
+// splits the register typed before a command, e.g. `"a` of `"ayy`.
+// returns `None` while the name is not typed yet
+pub fn split_register(input: &str) -> Option<(Option<char>, &str)> {
+    if !input.starts_with('"') {
+        return Some((None, input));
+    }
+    let name = input[1..].chars().next()?;
+    Some((Some(name), &input[1 + name.len_utf8()..]))
+}
+
 impl CommandWorker {
     fn apply_immediately_command(&mut self, state: &mut State) -> Option<Box<dyn EventWorker>> {
-        match self.input.as_str() {
-            "i" => state.current_panel_mut().cursor.go(Direction::Up, 1),
-            "j" => state.current_panel_mut().cursor.go(Direction::Left, 1),
-            "k" => state.current_panel_mut().cursor.go(Direction::Down, 1),
-            "l" => state.current_panel_mut().cursor.go(Direction::Right, 1),
+        let (register, input) = split_register(self.input.as_str())?;
+        if let Some(name) = register {
+            if !register::is_valid_name(name) {
+                state.update_message(format!("invalid register: {}", name).as_str());
+                self.input = String::new();
+                return None;
+            }
+        }
+        let (count, command) = split_count(input);
+        let n = count.unwrap_or(1);
+        if let Some(motion) = Motion::from_keys(command) {
+            state.move_cursor(motion, count);
+            self.input = String::new();
+            return None;
+        }
+        if let Some(operator) = Operator::from_keys(command) {
+            let keys = self.input.clone();
+            self.input = String::new();
+            return Some(Box::new(OperatorWorker::new(
+                operator, count, keys, register,
+            )));
+        }
+        match command {
+            "gi" => state.go_screen_rows(Direction::Up, n),
+            "gk" => state.go_screen_rows(Direction::Down, n),
+            "/" => return Some(Box::new(SearchWorker::new(state, SearchDirection::Forward))),
+            "?" => {
+                return Some(Box::new(SearchWorker::new(
+                    state,
+                    SearchDirection::Backward,
+                )))
+            }
+            "v" => return Some(Box::new(VisualWorker::new(state, VisualKind::Char))),
+            "V" => return Some(Box::new(VisualWorker::new(state, VisualKind::Line))),
+            "n" => state.search_next(false),
+            "N" => state.search_next(true),
+            "p" => state.put(register, count, false),
+            "P" => state.put(register, count, true),
+            "u" => state.undo(),
+            "U" => state.redo(),
+            "zt" => state.align_cursor_line(Alignment::Top),
+            "zz" => state.align_cursor_line(Alignment::Center),
+            "zb" => state.align_cursor_line(Alignment::Bottom),
             _ => return None,
         }
         self.input = String::new();
@@ -24,6 +93,23 @@ impl CommandWorker {
     }
 
     fn apply_buildin_command(&mut self, state: &mut State) -> Option<Box<dyn EventWorker>> {
+        // patterns may contain spaces, so substitutions are parsed before splitting
+        if self.input.starts_with(':') {
+            let current = state.current_panel().cursor.y;
+            let last = state.current_buffer().height() - 1;
+            match Substitute::parse(&self.input[1..], current, last) {
+                Some(Ok(substitute)) => {
+                    state.substitute(&substitute);
+                    return None;
+                }
+                Some(Err(msg)) => {
+                    state.update_message(msg.as_str());
+                    return None;
+                }
+                None => (),
+            }
+        }
+
         let mut inputs = self.input.split_whitespace();
         if let Some(command) = inputs.next() {
             let inputs: Vec<&str> = inputs.collect();
@@ -35,13 +121,97 @@ impl CommandWorker {
                         state.update_message("usage :go <direction> <distance>");
                     }
                 }
-                (":edit", []) => return Some(Box::new(EditWorker::default())),
+                // `:N` goes to the line N
+                (command, [])
+                    if command.starts_with(':') && command[1..].parse::<usize>().is_ok() =>
+                {
+                    let line = command[1..].parse().ok();
+                    state.move_cursor(Motion::FirstLine, line);
+                }
+                (":edit", []) if state.refuse_edit() => (),
+                (":edit", []) => {
+                    // all keystrokes typed until leaving Edit Mode are undone at once
+                    let cursor = state.current_panel().cursor.clone();
+                    state.current_buffer_mut().begin_undo_group(&cursor);
+                    return Some(Box::new(EditWorker::default()));
+                }
+                (":hex-edit", []) => {
+                    if !state.current_buffer().is_binary() {
+                        state.update_message("not a binary buffer, use :edit");
+                        return None;
+                    }
+                    // all bytes overwritten until leaving Hex Edit Mode are undone at once
+                    let cursor = state.current_panel().cursor.clone();
+                    state.current_buffer_mut().begin_undo_group(&cursor);
+                    return Some(Box::new(HexEditWorker::default()));
+                }
                 (":toggle-line-number", []) => {
                     state.current_panel_mut().is_visible_line_number =
                         !state.current_panel().is_visible_line_number;
                 }
-                (":save-as", [filename]) => state.current_buffer().save_as(filename).unwrap(), // TODO: remove unwrap
-                (":quit", []) => state.is_quit = true,
+                (":toggle-wrap", []) => {
+                    state.current_panel_mut().is_wrapped = !state.current_panel().is_wrapped;
+                }
+                // at most one sign, as `parse` takes
+                (":scroll", [n]) => match n.parse() {
+                    Ok(n) => state.scroll(n, ScrollUnit::Line),
+                    Err(_) => state.update_message("usage :scroll +<n> or :scroll -<n>"),
+                },
+                (":page-down", []) => state.scroll(1, ScrollUnit::Page),
+                (":page-up", []) => state.scroll(-1, ScrollUnit::Page),
+                (":half-page-down", []) => state.scroll(1, ScrollUnit::HalfPage),
+                (":half-page-up", []) => state.scroll(-1, ScrollUnit::HalfPage),
+                (":set", [option]) => state.set_option(option),
+                (":save-as", [filename]) => match state.current_buffer_mut().save_as(filename) {
+                    Ok(()) => state.update_message(format!("\"{}\" written", filename).as_str()),
+                    Err(err) => state.update_message(format!("{}", err).as_str()),
+                },
+                (":open", [path]) => state.open_buffer(path),
+                (":registers", []) => {
+                    let register_list = state.registers.list();
+                    state.update_message(register_list.as_str());
+                }
+                (":buffers", []) => {
+                    let buffer_list = state.buffer_list();
+                    state.update_message(buffer_list.as_str());
+                }
+                (":buffer", [n]) => match n.parse() {
+                    Ok(n) => state.show_nth_buffer(n),
+                    Err(_) => state.update_message("usage :buffer <n>"),
+                },
+                (":bnext", []) => state.cycle_buffer(1),
+                (":bprev", []) => state.cycle_buffer(-1),
+                (":bdelete", []) => state.delete_current_buffer(false),
+                (":bdelete!", []) => state.delete_current_buffer(true),
+                (":split", []) => state.split_panel(Direction::Up, None),
+                (":split", [path]) => state.split_panel(Direction::Up, Some(path)),
+                (":vsplit", []) => state.split_panel(Direction::Left, None),
+                (":vsplit", [path]) => state.split_panel(Direction::Left, Some(path)),
+                (":close", []) => state.close_current_panel(),
+                // at most one sign, as `parse` takes
+                (":resize", [delta]) => match delta.parse() {
+                    Ok(delta) => state.resize_current_panel(delta),
+                    Err(_) => state.update_message("usage :resize +<n> or :resize -<n>"),
+                },
+                (":focus", [dir]) => match dir.parse() {
+                    Ok(dir) => state.focus_panel(dir),
+                    Err(_) => state.update_message("usage :focus <direction>"),
+                },
+                (":pnext", []) => state.cycle_panel(1),
+                (":pprev", []) => state.cycle_panel(-1),
+                (":write", []) => {
+                    state.write_current_buffer();
+                }
+                (":write-quit", []) => {
+                    if state.write_current_buffer() {
+                        state.quit();
+                    }
+                }
+                (":nohighlight", []) => state.is_search_highlighted = false,
+                (":undo", []) => state.undo(),
+                (":redo", []) => state.redo(),
+                (":quit", []) => state.quit(),
+                (":quit!", []) => state.is_quit = true,
                 _ => {
                     // FIXME: do not use `{:?}`
                     state.update_message(
@@ -77,6 +247,13 @@ impl EventWorker for CommandWorker {
             Event::Key(Key::Backspace) => {
                 self.input.pop();
             }
+            Event::Key(Key::PageDown) => state.scroll(1, ScrollUnit::Page),
+            Event::Key(Key::PageUp) => state.scroll(-1, ScrollUnit::Page),
+            Event::Key(Key::Ctrl('d')) => state.scroll(1, ScrollUnit::HalfPage),
+            Event::Key(Key::Ctrl('u')) => state.scroll(-1, ScrollUnit::HalfPage),
+            Event::Key(Key::Ctrl('v')) => {
+                return Some(Box::new(VisualWorker::new(state, VisualKind::Block)))
+            }
             Event::Key(Key::Char(c)) => {
                 self.input.push(c);
                 state.update_message(self.input.as_str());
@@ -2,6 +2,7 @@ use termion::color::{Bg, Cyan, Reset};
 use termion::event::{Event, Key};
 
 use super::{command_worker::CommandWorker, EventWorker};
+use cursor::Cursor;
 use state::State;
 use util::Direction;
 
@@ -48,9 +49,33 @@ impl EventWorker for EditWorker {
                     .insert_at_cursor(c, &cursor);
                 state.current_panel_mut().cursor.go(Direction::Right, 1);
             }
-            Event::Key(Key::Esc) => return Some(Box::new(CommandWorker::default())),
+            Event::Key(Key::Esc) => {
+                state.current_buffer_mut().end_undo_group();
+                return Some(Box::new(CommandWorker::default()));
+            }
             _ => (),
         }
         None
     }
+
+    // inserted as one undo step of its own, apart from the keys typed around it
+    fn paste(&mut self, state: &mut State, text: &str) -> bool {
+        let cursor = state.current_panel().cursor.clone();
+        let buffer = state.current_buffer_mut();
+        buffer.end_undo_group();
+        buffer.replace_between(&cursor, &cursor, text, &cursor);
+        let next_cursor = match text.rfind('\n') {
+            Some(i) => Cursor {
+                x: text[i + 1..].chars().count(),
+                y: cursor.y + text.matches('\n').count(),
+            },
+            None => Cursor {
+                x: cursor.x + text.chars().count(),
+                y: cursor.y,
+            },
+        };
+        buffer.begin_undo_group(&next_cursor);
+        state.current_panel_mut().cursor = next_cursor;
+        true
+    }
 }
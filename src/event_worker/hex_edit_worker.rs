@@ -0,0 +1,88 @@
+use termion::color::{Bg, Reset, Yellow};
+use termion::event::{Event, Key};
+
+use super::{command_worker::CommandWorker, EventWorker};
+use buffer::BYTES_PER_ROW;
+use cursor::Cursor;
+use state::State;
+use util::Direction;
+
+// overwrites bytes of a binary buffer by typing two hex digits for each
+#[derive(Debug)]
+pub struct HexEditWorker {
+    // the high nibble typed for the byte at the cursor
+    high: Option<u8>,
+}
+
+impl Default for HexEditWorker {
+    fn default() -> Self {
+        HexEditWorker { high: None }
+    }
+}
+
+impl HexEditWorker {
+    // a typed high nibble is dropped when the cursor moves
+    fn go(&mut self, state: &mut State, dir: Direction) {
+        self.high = None;
+        state.current_panel_mut().cursor.go(dir, 1);
+    }
+}
+
+impl EventWorker for HexEditWorker {
+    fn mode(&self) -> String {
+        format!("{} Hex  {}", Bg(Yellow), Bg(Reset))
+    }
+
+    fn update(&mut self, state: &mut State, e: Event) -> Option<Box<dyn EventWorker>> {
+        match e {
+            Event::Key(Key::Char(c)) if c.is_ascii_hexdigit() => {
+                let nibble = c.to_digit(16).unwrap() as u8;
+                match self.high.take() {
+                    None => {
+                        self.high = Some(nibble);
+                        state.update_message(format!("{}_", c).as_str());
+                    }
+                    Some(high) => {
+                        let cursor = state.current_panel().cursor.clone();
+                        state
+                            .current_buffer_mut()
+                            .replace_byte_at_cursor(high << 4 | nibble, &cursor);
+                        state.update_message("");
+                        // goes on to the next byte, across rows
+                        let next = if cursor.x + 1 < BYTES_PER_ROW {
+                            Cursor {
+                                x: cursor.x + 1,
+                                y: cursor.y,
+                            }
+                        } else {
+                            Cursor {
+                                x: 0,
+                                y: cursor.y + 1,
+                            }
+                        };
+                        if next.y < state.current_buffer().height()
+                            && next.x < state.current_buffer().line_width_at(next.y)
+                        {
+                            state.current_panel_mut().cursor = next;
+                        }
+                    }
+                }
+            }
+            Event::Key(Key::Left) => self.go(state, Direction::Left),
+            Event::Key(Key::Right) => self.go(state, Direction::Right),
+            Event::Key(Key::Up) => self.go(state, Direction::Up),
+            Event::Key(Key::Down) => self.go(state, Direction::Down),
+            Event::Key(Key::Backspace) => {
+                self.high = None;
+                state.update_message("");
+            }
+            Event::Key(Key::Esc) => {
+                state.current_buffer_mut().end_undo_group();
+                return Some(Box::new(CommandWorker::default()));
+            }
+            Event::Key(Key::Char(_)) => state.update_message("type hex digits, Esc to leave"),
+            _ => (),
+        }
+        None
+    }
+}
@@ -0,0 +1,102 @@
+use termion::color::{Bg, Blue, Reset};
+use termion::event::{Event, Key};
+
+use super::{command_worker, command_worker::CommandWorker, edit_worker::EditWorker, EventWorker};
+use motion::Motion;
+use operator::{Operator, Range};
+use state::State;
+
+// waits for the motion after an operator, e.g. `w` of `dw`
+#[derive(Debug)]
+pub struct OperatorWorker {
+    operator: Operator,
+    // the count typed before the operator
+    count: Option<usize>,
+    // keys typed so far, shown in the status bar
+    keys: String,
+    // the register typed before the operator, e.g. `a` of `"ayw`
+    register: Option<char>,
+    input: String,
+}
+
+impl OperatorWorker {
+    pub fn new(
+        operator: Operator,
+        count: Option<usize>,
+        keys: String,
+        register: Option<char>,
+    ) -> Self {
+        OperatorWorker {
+            operator: operator,
+            count: count,
+            keys: keys,
+            register: register,
+            input: String::new(),
+        }
+    }
+
+    // returns the next worker once the motion is complete or invalid
+    fn apply(&mut self, state: &mut State) -> Option<Box<dyn EventWorker>> {
+        let (count, keys) = command_worker::split_count(self.input.as_str());
+        // `2d3w` deletes 6 words
+        let count = match (self.count, count) {
+            (Some(a), Some(b)) => Some(a * b),
+            (a, b) => a.or(b),
+        };
+        let range = if self.operator.is_line_key(keys) {
+            let line_i = state.current_panel().cursor.y;
+            Some(Range::lines(state.current_buffer(), line_i, count))
+        } else if let Some(motion) = Motion::from_keys(keys) {
+            let cursor = state.current_panel().cursor.clone();
+            Range::of_motion(
+                motion,
+                self.operator,
+                state.current_buffer(),
+                &cursor,
+                count,
+            )
+        } else if keys.is_empty() || keys == "g" {
+            // the motion is not complete yet
+            return None;
+        } else {
+            state.update_message(format!("invalid motion: {}", keys).as_str());
+            return Some(Box::new(CommandWorker::default()));
+        };
+
+        state.update_message("");
+        let is_changed = match range {
+            Some(range) => state.apply_operator(self.operator, range, self.register),
+            None => false,
+        };
+        if is_changed {
+            Some(Box::new(EditWorker::default()))
+        } else {
+            Some(Box::new(CommandWorker::default()))
+        }
+    }
+}
+
+impl EventWorker for OperatorWorker {
+    fn mode(&self) -> String {
+        format!("{} Op   {}", Bg(Blue), Bg(Reset))
+    }
+
+    fn update(&mut self, state: &mut State, e: Event) -> Option<Box<dyn EventWorker>> {
+        match e {
+            Event::Key(Key::Esc) => {
+                state.update_message("");
+                return Some(Box::new(CommandWorker::default()));
+            }
+            Event::Key(Key::Backspace) => {
+                if self.input.pop().is_none() {
+                    state.update_message("");
+                    return Some(Box::new(CommandWorker::default()));
+                }
+            }
+            Event::Key(Key::Char(c)) => self.input.push(c),
+            _ => return None,
+        }
+        state.update_message(format!("{}{}", self.keys, self.input).as_str());
+        self.apply(state)
+    }
+}
@@ -0,0 +1,97 @@
+use termion::color::{Bg, Reset, Yellow};
+use termion::event::{Event, Key};
+
+use super::{command_worker::CommandWorker, EventWorker};
+use cursor::Cursor;
+use search::{self, Search, SearchDirection};
+use state::State;
+
+#[derive(Debug)]
+pub struct SearchWorker {
+    pattern: String,
+    direction: SearchDirection,
+    origin: Cursor,
+}
+
+impl SearchWorker {
+    pub fn new(state: &mut State, direction: SearchDirection) -> Self {
+        let worker = SearchWorker {
+            pattern: String::new(),
+            direction: direction,
+            origin: state.current_panel().cursor.clone(),
+        };
+        worker.show_pattern(state);
+        worker
+    }
+
+    fn show_pattern(&self, state: &mut State) {
+        state.update_message(format!("{}{}", self.direction.mark(), self.pattern).as_str());
+    }
+
+    // moves the cursor to the match nearest to where the search started
+    fn search_incrementally(&self, state: &mut State) {
+        let regex = match search::compile(&self.pattern) {
+            Ok(regex) => regex,
+            Err(msg) => {
+                // maybe the pattern is still being typed, e.g. `foo(`
+                state.current_panel_mut().cursor = self.origin.clone();
+                state.update_message(msg.as_str());
+                return;
+            }
+        };
+        let found = search::find(state.current_buffer(), &regex, &self.origin, self.direction);
+        match found {
+            Some(found) => {
+                state.current_panel_mut().cursor = found.cursor;
+                self.show_pattern(state);
+            }
+            None => {
+                state.current_panel_mut().cursor = self.origin.clone();
+                if self.pattern.is_empty() {
+                    self.show_pattern(state);
+                } else {
+                    state.update_message(format!("pattern not found: {}", self.pattern).as_str());
+                }
+            }
+        }
+    }
+}
+
+impl EventWorker for SearchWorker {
+    fn mode(&self) -> String {
+        format!("{} Srch {}", Bg(Yellow), Bg(Reset))
+    }
+
+    fn update(&mut self, state: &mut State, e: Event) -> Option<Box<dyn EventWorker>> {
+        match e {
+            Event::Key(Key::Char('\n')) => {
+                if !self.pattern.is_empty() {
+                    state.search = Some(Search {
+                        pattern: self.pattern.clone(),
+                        direction: self.direction,
+                    });
+                    state.is_search_highlighted = true;
+                }
+                return Some(Box::new(CommandWorker::default()));
+            }
+            Event::Key(Key::Esc) => {
+                state.current_panel_mut().cursor = self.origin.clone();
+                state.update_message("");
+                return Some(Box::new(CommandWorker::default()));
+            }
+            Event::Key(Key::Backspace) => {
+                if self.pattern.pop().is_none() {
+                    state.update_message("");
+                    return Some(Box::new(CommandWorker::default()));
+                }
+                self.search_incrementally(state);
+            }
+            Event::Key(Key::Char(c)) => {
+                self.pattern.push(c);
+                self.search_incrementally(state);
+            }
+            _ => (),
+        }
+        None
+    }
+}
@@ -0,0 +1,148 @@
+use termion::color::{Bg, Green, Reset};
+use termion::event::{Event, Key};
+
+use super::{command_worker, command_worker::CommandWorker, edit_worker::EditWorker, EventWorker};
+use motion::Motion;
+use operator::Operator;
+use register;
+use selection::{Selection, VisualKind};
+use state::State;
+
+// selects text with motions, then applies an operator to it
+#[derive(Debug)]
+pub struct VisualWorker {
+    kind: VisualKind,
+    input: String,
+}
+
+impl VisualWorker {
+    // the selection starts at the cursor
+    pub fn new(state: &mut State, kind: VisualKind) -> Self {
+        state.selection = Some(Selection {
+            kind: kind,
+            anchor: state.current_panel().cursor.clone(),
+        });
+        VisualWorker {
+            kind: kind,
+            input: String::new(),
+        }
+    }
+
+    // `v`, `V` or Ctrl-v switches to the kind, or leaves Visual Mode if it is the current one
+    fn switch(&mut self, state: &mut State, kind: VisualKind) -> Option<Box<dyn EventWorker>> {
+        if self.kind == kind {
+            state.selection = None;
+            return Some(Box::new(CommandWorker::default()));
+        }
+        let anchor = match state.selection {
+            Some(ref selection) => selection.anchor.clone(),
+            None => state.current_panel().cursor.clone(),
+        };
+        state.selection = Some(Selection {
+            kind: kind,
+            anchor: anchor,
+        });
+        Some(Box::new(VisualWorker {
+            kind: kind,
+            input: String::new(),
+        }))
+    }
+
+    fn apply_operator(
+        &mut self,
+        state: &mut State,
+        operator: Operator,
+        register: Option<char>,
+    ) -> Option<Box<dyn EventWorker>> {
+        let selection = state.selection.take()?;
+        let cursor = state.current_panel().cursor.clone();
+        let range = selection.range(state.current_buffer(), &cursor);
+        if state.apply_operator(operator, range, register) {
+            Some(Box::new(EditWorker::default()))
+        } else {
+            Some(Box::new(CommandWorker::default()))
+        }
+    }
+
+    fn apply_command(&mut self, state: &mut State) -> Option<Box<dyn EventWorker>> {
+        let (register, input) = command_worker::split_register(self.input.as_str())?;
+        if let Some(name) = register {
+            if !register::is_valid_name(name) {
+                state.update_message(format!("invalid register: {}", name).as_str());
+                self.input = String::new();
+                return None;
+            }
+        }
+        let (count, keys) = command_worker::split_count(input);
+        if let Some(motion) = Motion::from_keys(keys) {
+            state.move_cursor(motion, count);
+            self.input = String::new();
+            return None;
+        }
+        let operator = match keys {
+            "d" | "x" => Operator::Delete,
+            "c" => Operator::Change,
+            "y" => Operator::Yank,
+            ">" => Operator::Indent,
+            "<" => Operator::Dedent,
+            "u" => Operator::Lowercase,
+            "U" => Operator::Uppercase,
+            "~" => Operator::ToggleCase,
+            "v" => return self.switch(state, VisualKind::Char),
+            "V" => return self.switch(state, VisualKind::Line),
+            // the other end of the selection
+            "o" => {
+                let cursor = state.current_panel().cursor.clone();
+                if let Some(ref mut selection) = state.selection {
+                    let anchor = ::std::mem::replace(&mut selection.anchor, cursor);
+                    state.current_panel_mut().cursor = anchor;
+                }
+                self.input = String::new();
+                return None;
+            }
+            // the motion or the char to replace with is not typed yet
+            "" | "g" | "r" => return None,
+            _ if keys.starts_with('r') => {
+                let c = keys[1..].chars().next().unwrap();
+                Operator::Replace(c)
+            }
+            _ => {
+                state.update_message(format!("invalid command: {}", keys).as_str());
+                self.input = String::new();
+                return None;
+            }
+        };
+        self.apply_operator(state, operator, register)
+    }
+}
+
+impl EventWorker for VisualWorker {
+    fn mode(&self) -> String {
+        let name = match self.kind {
+            VisualKind::Char => " Vis  ",
+            VisualKind::Line => " VLine",
+            VisualKind::Block => " VBlk ",
+        };
+        format!("{}{}{}", Bg(Green), name, Bg(Reset))
+    }
+
+    fn update(&mut self, state: &mut State, e: Event) -> Option<Box<dyn EventWorker>> {
+        match e {
+            Event::Key(Key::Esc) => {
+                state.selection = None;
+                return Some(Box::new(CommandWorker::default()));
+            }
+            Event::Key(Key::Ctrl('v')) => return self.switch(state, VisualKind::Block),
+            Event::Key(Key::Backspace) => {
+                self.input.pop();
+            }
+            Event::Key(Key::Char(c)) => {
+                self.input.push(c);
+                state.update_message(self.input.as_str());
+                return self.apply_command(state);
+            }
+            _ => (),
+        }
+        None
+    }
+}
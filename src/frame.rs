@@ -1,5 +1,26 @@
+use std::cmp::min;
+
 use util::Direction;
 
+// size of the line part of a split
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Fixed(usize),
+    //    numerator  denominator
+    Ratio(usize, usize),
+}
+
+impl Size {
+    // cells occupied out of `total` cells
+    pub fn cells(&self, total: usize) -> usize {
+        match *self {
+            Size::Fixed(cells) => min(cells, total),
+            Size::Ratio(_, 0) => 0,
+            Size::Ratio(numerator, denominator) => min(total * numerator / denominator, total),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Frame {
     pub x: usize,
@@ -19,7 +40,21 @@ impl Frame {
         }
     }
 
+    // length along `dir`
+    pub fn length(&self, dir: &Direction) -> usize {
+        match dir {
+            Direction::Up | Direction::Down => self.height,
+            Direction::Left | Direction::Right => self.width,
+        }
+    }
+
+    pub fn split_by(&self, dir: &Direction, size: &Size) -> (Frame, Frame) {
+        self.split(dir, size.cells(self.length(dir)))
+    }
+
+    // `line_width` larger than the frame is shrunk to fit in
     pub fn split(&self, dir: &Direction, line_width: usize) -> (Frame, Frame) {
+        let line_width = min(line_width, self.length(dir));
         match dir {
             Direction::Up => (
                 Frame {
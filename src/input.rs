@@ -0,0 +1,79 @@
+use std::io::stdin;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use signal_hook::iterator::Signals;
+use signal_hook::SIGWINCH;
+use termion::event::Event;
+use termion::input::TermReadEventsAndRaw;
+
+pub enum Input {
+    Event(Event),
+    // text pasted at once, between the marks of bracketed paste mode
+    Paste(String),
+    Resize,
+    // sent periodically, e.g. to show files loaded in the background
+    Tick,
+}
+
+const TICK_INTERVAL: Duration = Duration::from_millis(200);
+
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+// terminals may send line endings of pasted text as '\r'
+fn pasted_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+// key events, window size changes and ticks, each from its own thread
+pub fn inputs() -> Receiver<Input> {
+    let (sender, receiver) = channel();
+
+    let event_sender = sender.clone();
+    thread::spawn(move || {
+        // bytes pasted so far, which are not parsed as keys
+        let mut pasted: Option<Vec<u8>> = None;
+        for e in stdin().events_and_raw() {
+            let (e, bytes) = e.unwrap();
+            let input = match pasted.take() {
+                Some(text) if bytes.as_slice() == PASTE_END => Input::Paste(pasted_text(&text)),
+                Some(mut text) => {
+                    text.extend(bytes);
+                    pasted = Some(text);
+                    continue;
+                }
+                None if bytes.as_slice() == PASTE_START => {
+                    pasted = Some(vec![]);
+                    continue;
+                }
+                None => Input::Event(e),
+            };
+            if event_sender.send(input).is_err() {
+                break;
+            }
+        }
+    });
+
+    let tick_sender = sender.clone();
+    thread::spawn(move || loop {
+        thread::sleep(TICK_INTERVAL);
+        if tick_sender.send(Input::Tick).is_err() {
+            break;
+        }
+    });
+
+    let signals = Signals::new(&[SIGWINCH]).expect("can not watch window size changes");
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            if sender.send(Input::Resize).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
@@ -1,15 +1,28 @@
-use buffer::BufferId;
+use buffer::{Buffer, BufferId};
 use cursor::Cursor;
-use frame::Frame;
+use frame::{Frame, Size};
 use util::{clamp, Direction};
+use viewport::{Alignment, Viewport};
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 pub struct PanelName(pub String);
 
+use std::sync::RwLock;
+lazy_static! {
+    static ref PANEL_NAME_COUNT: RwLock<usize> = RwLock::new(0);
+}
+
 impl PanelName {
     pub fn new(name: &str) -> Self {
         PanelName(name.to_string())
     }
+
+    pub fn fresh() -> Self {
+        let mut fresh_count = PANEL_NAME_COUNT.write().unwrap();
+        let result = PanelName(format!("__panel{}__", *fresh_count));
+        *fresh_count += 1;
+        result
+    }
 }
 
 use std::fmt;
@@ -22,16 +35,64 @@ impl fmt::Display for PanelName {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Panel {
     pub cursor: Cursor,
-    pub path: Option<String>,
+    pub viewport: Viewport,
     pub buffer_id: BufferId,
     pub is_visible_line_number: bool,
     pub enable_syntax_highlight: bool,
+    pub is_wrapped: bool,
 }
 
 impl Panel {
-    pub fn fix_cursor_pos(&mut self, width: usize, height: usize) {
-        self.cursor.x = clamp(self.cursor.x, 0, width - 1);
-        self.cursor.y = clamp(self.cursor.y, 0, height - 1);
+    // the cursor may be just after the end of a line, to append there.
+    // in binary buffers it is always on a byte, since bytes are only overwritten
+    pub fn fix_cursor_pos(&mut self, buffer: &Buffer) {
+        self.cursor.y = clamp(self.cursor.y, 0, buffer.height().saturating_sub(1));
+        let width = buffer.line_width_at(self.cursor.y);
+        let max_x = if buffer.is_binary() {
+            width.saturating_sub(1)
+        } else {
+            width
+        };
+        self.cursor.x = clamp(self.cursor.x, 0, max_x);
+    }
+
+    // rows of the hex view are never wrapped
+    pub fn wraps(&self, buffer: &Buffer) -> bool {
+        self.is_wrapped && !buffer.is_binary()
+    }
+
+    // width of `frame` left for the text of `buffer`
+    pub fn text_width(&self, frame: &Frame, buffer: &Buffer) -> usize {
+        if self.is_visible_line_number {
+            frame
+                .width
+                .saturating_sub(Buffer::line_number_width(buffer.height()))
+        } else {
+            frame.width
+        }
+    }
+
+    // scrolls so that the cursor is in `frame`, away from the edges by `margin` lines
+    pub fn follow_cursor(&mut self, frame: &Frame, buffer: &Buffer, margin: usize) {
+        let text_width = self.text_width(frame, buffer);
+        self.viewport.follow(
+            &self.cursor,
+            buffer,
+            text_width,
+            frame.height,
+            margin,
+            self.wraps(buffer),
+        );
+    }
+
+    pub fn scroll(&mut self, n: isize, frame: &Frame, buffer: &Buffer, margin: usize) {
+        self.viewport
+            .scroll(n, &mut self.cursor, buffer, frame.height, margin);
+    }
+
+    pub fn align_cursor_line(&mut self, alignment: Alignment, frame: &Frame, margin: usize) {
+        self.viewport
+            .align(alignment, &self.cursor, frame.height, margin);
     }
 }
 
@@ -39,8 +100,8 @@ impl Panel {
 pub enum Layout {
     Panel(Panel, PanelName),
 
-    //    line pos  width    line          body
-    Lined(Direction, usize, Box<Layout>, Box<Layout>),
+    //    line pos  width line          body
+    Lined(Direction, Size, Box<Layout>, Box<Layout>),
 }
 
 impl Layout {
@@ -51,8 +112,8 @@ impl Layout {
     ) -> Result<T, E> {
         match self {
             Layout::Panel(panel, panel_name) => f(panel, panel_name, frame),
-            Layout::Lined(dir, line_width, line, body) => {
-                let (line_frame, body_frame) = frame.split(dir, *line_width);
+            Layout::Lined(dir, size, line, body) => {
+                let (line_frame, body_frame) = frame.split_by(dir, size);
                 body.traverse_impl(f, &body_frame)
                     .or(line.traverse_impl(f, &line_frame))
             }
@@ -73,8 +134,8 @@ impl Layout {
     ) -> Result<T, E> {
         match self {
             Layout::Panel(ref mut panel, ref mut panel_name) => f(panel, panel_name, frame),
-            Layout::Lined(dir, line_width, ref mut line, ref mut body) => {
-                let (line_frame, body_frame) = frame.split(dir, *line_width);
+            Layout::Lined(dir, size, ref mut line, ref mut body) => {
+                let (line_frame, body_frame) = frame.split_by(dir, size);
                 line.traverse_mut_impl(f, &line_frame)
                     .or(body.traverse_mut_impl(f, &body_frame))
             }
@@ -88,3 +149,144 @@ impl Layout {
         self.traverse_mut_impl(f, &Frame::screen())
     }
 }
+
+impl Layout {
+    fn panels_impl<'a>(
+        &'a self,
+        frame: &Frame,
+        result: &mut Vec<(&'a Panel, &'a PanelName, Frame)>,
+    ) {
+        match self {
+            Layout::Panel(panel, panel_name) => result.push((panel, panel_name, frame.clone())),
+            Layout::Lined(dir, size, line, body) => {
+                let (line_frame, body_frame) = frame.split_by(dir, size);
+                line.panels_impl(&line_frame, result);
+                body.panels_impl(&body_frame, result);
+            }
+        }
+    }
+
+    // every panel with its frame, from top-left to bottom-right
+    pub fn panels(&self) -> Vec<(&Panel, &PanelName, Frame)> {
+        let mut result = vec![];
+        self.panels_impl(&Frame::screen(), &mut result);
+        result
+    }
+
+    fn find_panel_layout_mut(&mut self, name: &PanelName) -> Option<&mut Layout> {
+        match self {
+            Layout::Panel(_, ref panel_name) if panel_name == name => Some(self),
+            Layout::Panel(_, _) => None,
+            Layout::Lined(_, _, ref mut line, ref mut body) => line
+                .find_panel_layout_mut(name)
+                .or_else(move || body.find_panel_layout_mut(name)),
+        }
+    }
+
+    // puts `new_panel` at `dir` side of the panel named `name`
+    pub fn split_panel(
+        &mut self,
+        name: &PanelName,
+        dir: Direction,
+        size: Size,
+        new_panel: Panel,
+        new_name: PanelName,
+    ) {
+        if let Some(layout) = self.find_panel_layout_mut(name) {
+            let old =
+                ::std::mem::replace(layout, Layout::Panel(new_panel.clone(), new_name.clone()));
+            *layout = Layout::Lined(
+                dir,
+                size,
+                Box::new(Layout::Panel(new_panel, new_name)),
+                Box::new(old),
+            );
+        }
+    }
+
+    // removes the panel named `name`, its sibling takes over its area.
+    // returns the name of a panel in the sibling
+    pub fn close_panel(&mut self, name: &PanelName) -> Option<PanelName> {
+        let sibling = match self {
+            Layout::Panel(_, _) => return None,
+            Layout::Lined(_, _, ref mut line, ref mut body) => match (&**line, &**body) {
+                (Layout::Panel(_, ref panel_name), _) if panel_name == name => {
+                    Some((**body).clone())
+                }
+                (_, Layout::Panel(_, ref panel_name)) if panel_name == name => {
+                    Some((**line).clone())
+                }
+                _ => None,
+            },
+        };
+        match sibling {
+            Some(sibling) => {
+                *self = sibling;
+                self.first_panel_name()
+            }
+            None => match self {
+                Layout::Panel(_, _) => None,
+                Layout::Lined(_, _, ref mut line, ref mut body) => line
+                    .close_panel(name)
+                    .or_else(move || body.close_panel(name)),
+            },
+        }
+    }
+
+    fn first_panel_name(&self) -> Option<PanelName> {
+        match self {
+            Layout::Panel(_, panel_name) => Some(panel_name.clone()),
+            Layout::Lined(_, _, line, _) => line.first_panel_name(),
+        }
+    }
+}
+
+impl Layout {
+    // `None` if the panel is not in this layout, otherwise whether it was resized
+    fn resize_panel_impl(&mut self, name: &PanelName, delta: isize, frame: &Frame) -> Option<bool> {
+        match self {
+            Layout::Panel(_, panel_name) => {
+                if panel_name == name {
+                    Some(false)
+                } else {
+                    None
+                }
+            }
+            Layout::Lined(dir, size, ref mut line, ref mut body) => {
+                let (line_frame, body_frame) = frame.split_by(dir, size);
+                let (is_in_line, is_resized) =
+                    if let Some(is_resized) = line.resize_panel_impl(name, delta, &line_frame) {
+                        (true, is_resized)
+                    } else {
+                        (false, body.resize_panel_impl(name, delta, &body_frame)?)
+                    };
+                if is_resized {
+                    return Some(true);
+                }
+                // fixed splits (e.g. the status bar) are never resized
+                if let Size::Ratio(_, _) = size {
+                    let total = frame.length(dir);
+                    if total < 2 {
+                        return Some(false);
+                    }
+                    let line_cells = size.cells(total) as isize;
+                    let line_cells = if is_in_line {
+                        line_cells + delta
+                    } else {
+                        line_cells - delta
+                    };
+                    let line_cells = clamp(line_cells, 1, total as isize - 1);
+                    *size = Size::Ratio(line_cells as usize, total);
+                    return Some(true);
+                }
+                Some(false)
+            }
+        }
+    }
+
+    // grows the panel named `name` by `delta` cells (or shrinks if negative)
+    // against its nearest proportional split. returns whether it was resized
+    pub fn resize_panel(&mut self, name: &PanelName, delta: isize) -> bool {
+        self.resize_panel_impl(name, delta, &Frame::screen()) == Some(true)
+    }
+}
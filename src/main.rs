@@ -1,40 +1,96 @@
 #![feature(try_trait)]
+#![cfg_attr(test, feature(test))]
 
 #[macro_use]
 extern crate lazy_static;
 extern crate dirs;
+extern crate encoding_rs;
+extern crate memmap;
+extern crate regex;
 extern crate serde;
 extern crate serde_derive;
+extern crate signal_hook;
 extern crate termion;
 extern crate toml;
+extern crate unicode_width;
 
-use std::io::stdin;
+#[cfg(test)]
+extern crate test;
 
-use termion::input::TermRead;
+use input::Input;
 
 mod buffer;
+mod clipboard;
 mod config;
 mod cursor;
 mod drawer;
 mod editor;
+mod error;
 mod event_worker;
 mod frame;
+mod input;
 mod layout;
+mod motion;
+mod operator;
+mod register;
+mod search;
+mod selection;
 mod state;
 mod status;
+mod substitute;
 mod util;
+mod viewport;
+
+const USAGE: &str = "usage: ysd [--help] [--version] [--encoding <encoding>] [<filename>...]";
 
 fn main() {
-    let args: Vec<_> = ::std::env::args().collect();
-    if args.len() != 2 {
-        panic!("error: filename was not given.");
+    let mut filenames = vec![];
+    let mut encoding = None;
+    let mut args = ::std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "-v" | "--version" => {
+                println!("ysd {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            "-e" | "--encoding" => {
+                let label = args.next().unwrap_or_default();
+                match encoding_rs::Encoding::for_label(label.as_bytes()) {
+                    Some(e) => encoding = Some(e),
+                    None => {
+                        eprintln!("error: unknown encoding \"{}\"\n{}", label, USAGE);
+                        ::std::process::exit(1);
+                    }
+                }
+            }
+            arg if arg.starts_with('-') => {
+                eprintln!("error: unknown option {}\n{}", arg, USAGE);
+                ::std::process::exit(1);
+            }
+            _ => filenames.push(arg),
+        }
     }
-    let stdin = stdin();
-    let mut editor = editor::Editor::from_file(&args[1]);
+
+    let mut editor = editor::Editor::from_files(&filenames, encoding).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        ::std::process::exit(1);
+    });
     editor.draw();
-    for e in stdin.events() {
-        let e = e.unwrap();
-        editor.update(e);
+    for input in input::inputs() {
+        match input {
+            Input::Event(e) => editor.update(e),
+            Input::Paste(text) => editor.paste(&text),
+            Input::Resize => editor.resize(),
+            Input::Tick => {
+                if !editor.tick() {
+                    continue;
+                }
+            }
+        }
         if editor.state.is_quit {
             break;
         }
@@ -0,0 +1,441 @@
+use std::cmp::min;
+
+use buffer::Buffer;
+use cursor::Cursor;
+use util::Direction;
+
+// cursor moves in Command Mode, which take a count typed before them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Go(Direction),
+    NextWordStart,
+    PrevWordStart,
+    NextWordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    // the line of the count if it is given
+    FirstLine,
+    LastLine,
+    MatchingBracket,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Blank,
+    // letters, digits and '_'
+    Word,
+    Punct,
+}
+
+fn class_of(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punct
+    }
+}
+
+// walks over the chars of a buffer. the end of a line is a position too,
+// of the newline which is a blank
+struct Walker<'a> {
+    buffer: &'a Buffer,
+    line: Vec<char>,
+    cursor: Cursor,
+}
+
+impl<'a> Walker<'a> {
+    fn new(buffer: &'a Buffer, cursor: &Cursor) -> Self {
+        let line = buffer.line_at(cursor.y);
+        let x = min(cursor.x, line.len());
+        Walker {
+            buffer: buffer,
+            line: line,
+            cursor: Cursor { x: x, y: cursor.y },
+        }
+    }
+
+    fn char(&self) -> Option<char> {
+        self.line.get(self.cursor.x).cloned()
+    }
+
+    fn class(&self) -> CharClass {
+        self.char().map_or(CharClass::Blank, class_of)
+    }
+    fn class_before(&self) -> CharClass {
+        match self.cursor.x {
+            0 => CharClass::Blank,
+            x => class_of(self.line[x - 1]),
+        }
+    }
+    fn class_after(&self) -> CharClass {
+        self.line
+            .get(self.cursor.x + 1)
+            .map_or(CharClass::Blank, |c| class_of(*c))
+    }
+
+    // empty lines are words by themselves
+    fn is_empty_line(&self) -> bool {
+        self.line.is_empty()
+    }
+
+    // returns false at the end of the buffer
+    fn next(&mut self) -> bool {
+        if self.cursor.x < self.line.len() {
+            self.cursor.x += 1;
+        } else if self.cursor.y + 1 < self.buffer.height() {
+            self.cursor.y += 1;
+            self.cursor.x = 0;
+            self.line = self.buffer.line_at(self.cursor.y);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // returns false at the beginning of the buffer
+    fn prev(&mut self) -> bool {
+        if self.cursor.x > 0 {
+            self.cursor.x -= 1;
+        } else if self.cursor.y > 0 {
+            self.cursor.y -= 1;
+            self.line = self.buffer.line_at(self.cursor.y);
+            self.cursor.x = self.line.len();
+        } else {
+            return false;
+        }
+        true
+    }
+
+    fn next_word_start(&mut self) {
+        let start = self.class();
+        if !self.next() {
+            return;
+        }
+        if start != CharClass::Blank {
+            while self.class() == start {
+                if !self.next() {
+                    return;
+                }
+            }
+        }
+        while self.class() == CharClass::Blank && !self.is_empty_line() {
+            if !self.next() {
+                return;
+            }
+        }
+    }
+
+    fn prev_word_start(&mut self) {
+        if !self.prev() {
+            return;
+        }
+        while self.class() == CharClass::Blank && !self.is_empty_line() {
+            if !self.prev() {
+                return;
+            }
+        }
+        let class = self.class();
+        while class != CharClass::Blank && self.class_before() == class {
+            self.prev();
+        }
+    }
+
+    fn next_word_end(&mut self) {
+        if !self.next() {
+            return;
+        }
+        while self.class() == CharClass::Blank {
+            if !self.next() {
+                return;
+            }
+        }
+        let class = self.class();
+        while self.class_after() == class {
+            self.next();
+        }
+    }
+
+    // the bracket matching the first one at or after the cursor in the line
+    fn matching_bracket(&mut self) -> Option<()> {
+        const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+        let x = self.line[self.cursor.x..]
+            .iter()
+            .position(|c| PAIRS.iter().any(|(open, close)| c == open || c == close))?;
+        self.cursor.x += x;
+        let c = self.char()?;
+        let (target, is_forward) = PAIRS.iter().find_map(|&(open, close)| {
+            if c == open {
+                Some((close, true))
+            } else if c == close {
+                Some((open, false))
+            } else {
+                None
+            }
+        })?;
+        let mut depth = 0;
+        loop {
+            let moved = if is_forward { self.next() } else { self.prev() };
+            if !moved {
+                return None;
+            }
+            match self.char() {
+                Some(d) if d == c => depth += 1,
+                Some(d) if d == target && depth == 0 => return Some(()),
+                Some(d) if d == target => depth -= 1,
+                _ => (),
+            }
+        }
+    }
+}
+
+pub fn first_non_blank(buffer: &Buffer, line_i: usize) -> usize {
+    let line = buffer.line_at(line_i);
+    line.iter()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(line.len())
+}
+
+// whether `cursor` is on the last char of a word
+pub fn is_word_end(buffer: &Buffer, cursor: &Cursor) -> bool {
+    let walker = Walker::new(buffer, cursor);
+    walker.class() != CharClass::Blank && walker.class_after() != walker.class()
+}
+
+impl Motion {
+    // keys of the motion in Command Mode
+    pub fn from_keys(keys: &str) -> Option<Self> {
+        let motion = match keys {
+            "i" => Motion::Go(Direction::Up),
+            "j" => Motion::Go(Direction::Left),
+            "k" => Motion::Go(Direction::Down),
+            "l" => Motion::Go(Direction::Right),
+            "w" => Motion::NextWordStart,
+            "b" => Motion::PrevWordStart,
+            "e" => Motion::NextWordEnd,
+            "0" => Motion::LineStart,
+            "^" => Motion::FirstNonBlank,
+            "$" => Motion::LineEnd,
+            "gg" => Motion::FirstLine,
+            "G" => Motion::LastLine,
+            "%" => Motion::MatchingBracket,
+            _ => return None,
+        };
+        Some(motion)
+    }
+
+    // operators apply to whole lines of linewise motions
+    pub fn is_linewise(&self) -> bool {
+        match self {
+            Motion::Go(Direction::Up)
+            | Motion::Go(Direction::Down)
+            | Motion::FirstLine
+            | Motion::LastLine => true,
+            _ => false,
+        }
+    }
+
+    // operators apply to the char at the end of inclusive motions too
+    pub fn is_inclusive(&self) -> bool {
+        match self {
+            Motion::NextWordEnd | Motion::MatchingBracket => true,
+            _ => false,
+        }
+    }
+
+    // where `cursor` goes by the motion repeated `count` times,
+    // or `None` if it can not move
+    pub fn apply(&self, buffer: &Buffer, cursor: &Cursor, count: Option<usize>) -> Option<Cursor> {
+        let n = count.unwrap_or(1);
+        let last_line = buffer.height().saturating_sub(1);
+        let mut walker = Walker::new(buffer, cursor);
+        match self {
+            Motion::Go(dir) => {
+                let mut cursor = cursor.clone();
+                cursor.go(*dir, n);
+                cursor.y = min(cursor.y, last_line);
+                return Some(cursor);
+            }
+            Motion::NextWordStart => (0..n).for_each(|_| walker.next_word_start()),
+            Motion::PrevWordStart => (0..n).for_each(|_| walker.prev_word_start()),
+            Motion::NextWordEnd => (0..n).for_each(|_| walker.next_word_end()),
+            Motion::LineStart => walker.cursor.x = 0,
+            Motion::FirstNonBlank => walker.cursor.x = first_non_blank(buffer, cursor.y),
+            // `3$` goes to the end of the 2nd line below
+            Motion::LineEnd => {
+                let y = min(cursor.y + n - 1, last_line);
+                walker.cursor = Cursor {
+                    x: buffer.line_width_at(y),
+                    y: y,
+                };
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let y = match count {
+                    Some(count) => min(count.saturating_sub(1), last_line),
+                    None if *self == Motion::FirstLine => 0,
+                    None => last_line,
+                };
+                walker.cursor = Cursor {
+                    x: first_non_blank(buffer, y),
+                    y: y,
+                };
+            }
+            Motion::MatchingBracket => walker.matching_bracket()?,
+        }
+        Some(walker.cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer_of(text: &str) -> Buffer {
+        let mut buffer = Buffer::empty();
+        buffer.push(text.to_string());
+        buffer
+    }
+
+    fn apply(
+        motion: Motion,
+        buffer: &Buffer,
+        (x, y): (usize, usize),
+        count: Option<usize>,
+    ) -> Option<(usize, usize)> {
+        motion
+            .apply(buffer, &Cursor { x: x, y: y }, count)
+            .map(|cursor| (cursor.x, cursor.y))
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let buffer = buffer_of("foo.bar  baz\n\n  qux");
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (0, 0), None),
+            Some((3, 0))
+        );
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (3, 0), None),
+            Some((4, 0))
+        );
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (0, 0), Some(3)),
+            Some((9, 0))
+        );
+        // an empty line is a word, and blanks at the start of a line are skipped
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (9, 0), None),
+            Some((0, 1))
+        );
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (0, 1), None),
+            Some((2, 2))
+        );
+        // stops at the end of the buffer
+        assert_eq!(
+            apply(Motion::NextWordStart, &buffer, (2, 2), Some(5)),
+            Some((5, 2))
+        );
+
+        assert_eq!(
+            apply(Motion::PrevWordStart, &buffer, (2, 2), None),
+            Some((0, 1))
+        );
+        assert_eq!(
+            apply(Motion::PrevWordStart, &buffer, (0, 1), None),
+            Some((9, 0))
+        );
+        assert_eq!(
+            apply(Motion::PrevWordStart, &buffer, (9, 0), Some(2)),
+            Some((3, 0))
+        );
+
+        assert_eq!(
+            apply(Motion::NextWordEnd, &buffer, (0, 0), None),
+            Some((2, 0))
+        );
+        assert_eq!(
+            apply(Motion::NextWordEnd, &buffer, (2, 0), Some(2)),
+            Some((6, 0))
+        );
+        // empty lines are skipped by `e`
+        assert_eq!(
+            apply(Motion::NextWordEnd, &buffer, (11, 0), None),
+            Some((4, 2))
+        );
+        assert!(is_word_end(&buffer, &Cursor { x: 2, y: 0 }));
+        assert!(!is_word_end(&buffer, &Cursor { x: 1, y: 0 }));
+    }
+
+    #[test]
+    fn test_matching_bracket() {
+        let buffer = buffer_of("f(a(b)c) [x]\n{\n  {}\n}\n(()");
+        // the first bracket at or after the cursor, skipping nested pairs
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (0, 0), None),
+            Some((7, 0))
+        );
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (7, 0), None),
+            Some((1, 0))
+        );
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (3, 0), None),
+            Some((5, 0))
+        );
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (8, 0), None),
+            Some((11, 0))
+        );
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (0, 1), None),
+            Some((0, 3))
+        );
+        assert_eq!(
+            apply(Motion::MatchingBracket, &buffer, (0, 3), None),
+            Some((0, 1))
+        );
+        // unbalanced, or no bracket after the cursor
+        assert_eq!(apply(Motion::MatchingBracket, &buffer, (0, 4), None), None);
+        assert_eq!(apply(Motion::MatchingBracket, &buffer, (4, 2), None), None);
+    }
+
+    #[test]
+    fn test_counts_are_clamped() {
+        let buffer = buffer_of("ab\ncde\nf\n  gh");
+        assert_eq!(apply(Motion::LineEnd, &buffer, (0, 0), None), Some((2, 0)));
+        assert_eq!(
+            apply(Motion::LineEnd, &buffer, (0, 0), Some(3)),
+            Some((1, 2))
+        );
+        assert_eq!(
+            apply(Motion::LineEnd, &buffer, (0, 2), Some(3)),
+            Some((4, 3))
+        );
+
+        assert_eq!(apply(Motion::LastLine, &buffer, (1, 0), None), Some((2, 3)));
+        assert_eq!(
+            apply(Motion::LastLine, &buffer, (1, 0), Some(2)),
+            Some((0, 1))
+        );
+        assert_eq!(
+            apply(Motion::LastLine, &buffer, (1, 0), Some(5)),
+            Some((2, 3))
+        );
+        assert_eq!(
+            apply(Motion::FirstLine, &buffer, (1, 3), None),
+            Some((0, 0))
+        );
+        assert_eq!(
+            apply(Motion::FirstLine, &buffer, (1, 3), Some(0)),
+            Some((0, 0))
+        );
+
+        assert_eq!(
+            apply(Motion::Go(Direction::Down), &buffer, (0, 1), Some(9)),
+            Some((0, 3))
+        );
+    }
+}
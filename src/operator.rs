@@ -0,0 +1,437 @@
+use std::cmp::{max, min};
+
+use buffer::Buffer;
+use cursor::Cursor;
+use motion::{self, Motion};
+use viewport;
+
+// edits applied to the text a motion moves over, e.g. `dw`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+    // replaces every char with the char
+    Replace(char),
+}
+
+impl Operator {
+    // keys of the operator in Command Mode
+    pub fn from_keys(keys: &str) -> Option<Self> {
+        let operator = match keys {
+            "d" => Operator::Delete,
+            "c" => Operator::Change,
+            "y" => Operator::Yank,
+            ">" => Operator::Indent,
+            "<" => Operator::Dedent,
+            "gu" => Operator::Lowercase,
+            "gU" => Operator::Uppercase,
+            "g~" => Operator::ToggleCase,
+            _ => return None,
+        };
+        Some(operator)
+    }
+
+    // typed after the operator to apply it to lines, e.g. `d` of `dd` or `u` of `guu`
+    pub fn is_line_key(&self, key: &str) -> bool {
+        match self {
+            Operator::Delete => key == "d",
+            Operator::Change => key == "c",
+            Operator::Yank => key == "y",
+            Operator::Indent => key == ">",
+            Operator::Dedent => key == "<",
+            Operator::Lowercase => key == "u" || key == "gu",
+            Operator::Uppercase => key == "U" || key == "gU",
+            Operator::ToggleCase => key == "~" || key == "g~",
+            Operator::Replace(_) => false,
+        }
+    }
+
+    // applies the operator to a text
+    pub fn convert(&self, text: &str) -> String {
+        match self {
+            Operator::Lowercase => text.to_lowercase(),
+            Operator::Uppercase => text.to_uppercase(),
+            Operator::ToggleCase => text
+                .chars()
+                .map(|c| {
+                    if c.is_uppercase() {
+                        c.to_lowercase().collect::<String>()
+                    } else {
+                        c.to_uppercase().collect::<String>()
+                    }
+                })
+                .collect(),
+            Operator::Replace(c) => text
+                .chars()
+                .map(|d| if d == '\n' { d } else { *c })
+                .collect(),
+            _ => text.to_string(),
+        }
+    }
+}
+
+// `line` indented by `width` spaces, or dedented by a tab or at most `width` spaces
+pub fn shift_line(line: &str, operator: Operator, width: usize) -> String {
+    match operator {
+        Operator::Indent if !line.is_empty() => format!("{}{}", " ".repeat(width), line),
+        Operator::Dedent if line.starts_with('\t') => line[1..].to_string(),
+        Operator::Dedent => {
+            let n_spaces = line.chars().take(width).take_while(|c| *c == ' ').count();
+            line[n_spaces..].to_string()
+        }
+        _ => line.to_string(),
+    }
+}
+
+// the text deleted, changed or yanked by an operator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Yanked {
+    pub text: String,
+    // whole lines, each ending with '\n'
+    pub is_linewise: bool,
+}
+
+// the part of a buffer an operator applies to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Range {
+    // from the first cursor up to the second one, excluding it
+    Chars(Cursor, Cursor),
+    // from the first line to the second one, including it
+    Lines(usize, usize),
+    // the display columns from `left` up to `right` (excluding it) of each line,
+    // so that a block is a rectangle on the screen with wide chars and tabs
+    Block {
+        first: usize,
+        last: usize,
+        left: usize,
+        right: usize,
+    },
+}
+
+pub fn is_before(a: &Cursor, b: &Cursor) -> bool {
+    (a.y, a.x) < (b.y, b.x)
+}
+
+// the position just after the char at `cursor`, or the beginning
+// of the next line if `cursor` is at the end of a line
+pub fn next_position(buffer: &Buffer, cursor: &Cursor) -> Cursor {
+    if cursor.x < buffer.line_width_at(cursor.y) {
+        Cursor {
+            x: cursor.x + 1,
+            y: cursor.y,
+        }
+    } else if cursor.y + 1 < buffer.height() {
+        Cursor {
+            x: 0,
+            y: cursor.y + 1,
+        }
+    } else {
+        cursor.clone()
+    }
+}
+
+// chars of `line` at least partly in the display columns `left..right`,
+// from and up to (excluding)
+fn chars_in_columns(line: &[char], left: usize, right: usize) -> (usize, usize) {
+    let positions = viewport::char_positions(line, 0, false);
+    let start = (0..line.len())
+        .find(|&i| positions[i + 1].0 > left)
+        .unwrap_or(line.len());
+    let end = (start..line.len())
+        .find(|&i| positions[i].0 >= right)
+        .unwrap_or(line.len());
+    (start, end)
+}
+
+impl Range {
+    // the range `cursor` moves over by `motion`, or `None` if it can not move
+    pub fn of_motion(
+        motion: Motion,
+        operator: Operator,
+        buffer: &Buffer,
+        cursor: &Cursor,
+        count: Option<usize>,
+    ) -> Option<Self> {
+        // `cw` on a word changes only the rest of the word as `ce` does, leaving the blanks
+        // after it. on the last char of a word, it stops there instead of the next word end
+        let is_on_word = buffer
+            .line_at(cursor.y)
+            .get(cursor.x)
+            .map_or(false, |c| !c.is_whitespace());
+        if operator == Operator::Change && motion == Motion::NextWordStart && is_on_word {
+            let n = count.unwrap_or(1);
+            let end = if !motion::is_word_end(buffer, cursor) {
+                Motion::NextWordEnd.apply(buffer, cursor, count)?
+            } else if n > 1 {
+                Motion::NextWordEnd.apply(buffer, cursor, Some(n - 1))?
+            } else {
+                cursor.clone()
+            };
+            return Some(Range::Chars(cursor.clone(), next_position(buffer, &end)));
+        }
+
+        let target = motion.apply(buffer, cursor, count)?;
+        if motion.is_linewise() {
+            return Some(Range::Lines(
+                min(cursor.y, target.y),
+                max(cursor.y, target.y),
+            ));
+        }
+        let cursor = Cursor {
+            x: min(cursor.x, buffer.line_width_at(cursor.y)),
+            y: cursor.y,
+        };
+        let (start, mut end) = if is_before(&target, &cursor) {
+            (target, cursor)
+        } else {
+            (cursor, target)
+        };
+        if motion.is_inclusive() {
+            end = next_position(buffer, &end);
+        } else if end.y > start.y && end.x <= motion::first_non_blank(buffer, end.y) {
+            // e.g. `dw` on the last word of a line does not join the next line
+            end.y -= 1;
+            end.x = buffer.line_width_at(end.y);
+        }
+        Some(Range::Chars(start, end))
+    }
+
+    // `count` lines from `line_i`, as `3dd` applies to
+    pub fn lines(buffer: &Buffer, line_i: usize, count: Option<usize>) -> Self {
+        let last_line = buffer.height().saturating_sub(1);
+        let n = count.unwrap_or(1);
+        Range::Lines(line_i, min(line_i + n - 1, last_line))
+    }
+
+    pub fn is_linewise(&self) -> bool {
+        match self {
+            Range::Lines(_, _) => true,
+            _ => false,
+        }
+    }
+
+    // the first and the last line of the range
+    pub fn line_span(&self) -> (usize, usize) {
+        match self {
+            Range::Chars(start, end) => (start.y, end.y),
+            Range::Lines(first, last) | Range::Block { first, last, .. } => (*first, *last),
+        }
+    }
+
+    // starts and ends of the text of the range, without the newline after lines.
+    // a block has a part in each line
+    pub fn parts(&self, buffer: &Buffer) -> Vec<(Cursor, Cursor)> {
+        match self {
+            Range::Chars(start, end) => vec![(start.clone(), end.clone())],
+            Range::Lines(first, last) => vec![(
+                Cursor { x: 0, y: *first },
+                Cursor {
+                    x: buffer.line_width_at(*last),
+                    y: *last,
+                },
+            )],
+            Range::Block {
+                first,
+                last,
+                left,
+                right,
+            } => (*first..*last + 1)
+                .map(|y| {
+                    let (start, end) = chars_in_columns(&buffer.line_at(y), *left, *right);
+                    (Cursor { x: start, y: y }, Cursor { x: end, y: y })
+                })
+                .collect(),
+        }
+    }
+
+    // chars of the line `line_i` in the range, from and up to (excluding)
+    pub fn chars_in_line(&self, buffer: &Buffer, line_i: usize) -> Option<(usize, usize)> {
+        let (first, last) = self.line_span();
+        if line_i < first || last < line_i {
+            return None;
+        }
+        let width = buffer.line_width_at(line_i);
+        match self {
+            Range::Chars(start, end) => Some((
+                if line_i == start.y { start.x } else { 0 },
+                if line_i == end.y { end.x } else { width },
+            )),
+            Range::Lines(_, _) => Some((0, width)),
+            Range::Block { left, right, .. } => {
+                Some(chars_in_columns(&buffer.line_at(line_i), *left, *right))
+            }
+        }
+    }
+
+    // start and end of the text removed by deleting lines.
+    // they take a newline with them, the one before them at the end of the buffer
+    pub fn delete_bounds(&self, buffer: &Buffer) -> (Cursor, Cursor) {
+        match self {
+            Range::Lines(first, last) if last + 1 < buffer.height() => {
+                (Cursor { x: 0, y: *first }, Cursor { x: 0, y: last + 1 })
+            }
+            Range::Lines(first, last) if *first > 0 => (
+                Cursor {
+                    x: buffer.line_width_at(first - 1),
+                    y: first - 1,
+                },
+                Cursor {
+                    x: buffer.line_width_at(*last),
+                    y: *last,
+                },
+            ),
+            _ => self.parts(buffer)[0].clone(),
+        }
+    }
+
+    // the text of a block is its parts joined by '\n'
+    pub fn yank(&self, buffer: &Buffer) -> Yanked {
+        let parts: Vec<String> = self
+            .parts(buffer)
+            .iter()
+            .map(|(start, end)| buffer.text_between(start, end))
+            .collect();
+        let mut text = parts.join("\n");
+        if self.is_linewise() {
+            text.push('\n');
+        }
+        Yanked {
+            text: text,
+            is_linewise: self.is_linewise(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::Direction;
+
+    fn buffer_of(text: &str) -> Buffer {
+        let mut buffer = Buffer::empty();
+        buffer.push(text.to_string());
+        buffer
+    }
+
+    fn chars(start: (usize, usize), end: (usize, usize)) -> Range {
+        Range::Chars(
+            Cursor {
+                x: start.0,
+                y: start.1,
+            },
+            Cursor { x: end.0, y: end.1 },
+        )
+    }
+
+    #[test]
+    fn test_of_motion() {
+        let buffer = buffer_of("foo bar\n  baz");
+        let range = |motion, operator, x, count| {
+            Range::of_motion(motion, operator, &buffer, &Cursor { x: x, y: 0 }, count)
+        };
+        let (d, c) = (Operator::Delete, Operator::Change);
+        assert_eq!(
+            range(Motion::NextWordStart, d, 0, None),
+            Some(chars((0, 0), (4, 0)))
+        );
+        // `dw` on the last word of a line stops at the end of the line
+        assert_eq!(
+            range(Motion::NextWordStart, d, 4, None),
+            Some(chars((4, 0), (7, 0)))
+        );
+        assert_eq!(
+            range(Motion::NextWordEnd, d, 0, None),
+            Some(chars((0, 0), (3, 0)))
+        );
+        assert_eq!(
+            range(Motion::PrevWordStart, d, 4, None),
+            Some(chars((0, 0), (4, 0)))
+        );
+        assert_eq!(
+            range(Motion::Go(Direction::Down), d, 2, None),
+            Some(Range::Lines(0, 1))
+        );
+
+        // `cw` changes up to the end of the word, even on its last char
+        assert_eq!(
+            range(Motion::NextWordStart, c, 0, None),
+            Some(chars((0, 0), (3, 0)))
+        );
+        assert_eq!(
+            range(Motion::NextWordStart, c, 2, None),
+            Some(chars((2, 0), (3, 0)))
+        );
+        assert_eq!(
+            range(Motion::NextWordStart, c, 2, Some(2)),
+            Some(chars((2, 0), (7, 0)))
+        );
+        assert_eq!(
+            range(Motion::NextWordStart, c, 1, Some(2)),
+            Some(chars((1, 0), (7, 0)))
+        );
+        // on blanks as `dw`
+        assert_eq!(
+            range(Motion::NextWordStart, c, 3, None),
+            Some(chars((3, 0), (4, 0)))
+        );
+    }
+
+    #[test]
+    fn test_delete_bounds() {
+        let bounds = |buffer: &Buffer, first, last| {
+            let (start, end) = Range::Lines(first, last).delete_bounds(buffer);
+            ((start.x, start.y), (end.x, end.y))
+        };
+        let buffer = buffer_of("a\nbc\nd");
+        assert_eq!(bounds(&buffer, 0, 0), ((0, 0), (0, 1)));
+        assert_eq!(bounds(&buffer, 0, 1), ((0, 0), (0, 2)));
+        // the last lines take the newline before them
+        assert_eq!(bounds(&buffer, 2, 2), ((2, 1), (1, 2)));
+        assert_eq!(bounds(&buffer, 1, 2), ((1, 0), (1, 2)));
+        // all lines leave an empty line
+        assert_eq!(bounds(&buffer, 0, 2), ((0, 0), (1, 2)));
+        assert_eq!(bounds(&buffer_of("abc"), 0, 0), ((0, 0), (3, 0)));
+    }
+
+    #[test]
+    fn test_shift_line() {
+        assert_eq!(shift_line("a", Operator::Indent, 4), "    a");
+        assert_eq!(shift_line("", Operator::Indent, 4), "");
+        assert_eq!(shift_line("\t\ta", Operator::Dedent, 4), "\ta");
+        assert_eq!(shift_line("\t  a", Operator::Dedent, 4), "  a");
+        assert_eq!(shift_line("      a", Operator::Dedent, 4), "  a");
+        assert_eq!(shift_line("  \ta", Operator::Dedent, 4), "\ta");
+        assert_eq!(shift_line("a", Operator::Dedent, 4), "a");
+    }
+
+    #[test]
+    fn test_chars_in_columns() {
+        let line: Vec<char> = "a\tb漢c".chars().collect();
+        // chars partly in the columns are included
+        assert_eq!(chars_in_columns(&line, 2, 11), (1, 4));
+        assert_eq!(chars_in_columns(&line, 3, 5), (1, 2));
+        assert_eq!(chars_in_columns(&line, 10, 11), (3, 4));
+        assert_eq!(chars_in_columns(&line, 0, 1), (0, 1));
+        let line: Vec<char> = "漢字\tx".chars().collect();
+        assert_eq!(chars_in_columns(&line, 2, 11), (1, 4));
+        assert_eq!(chars_in_columns(&line, 1, 2), (0, 1));
+        assert_eq!(chars_in_columns(&line, 8, 9), (3, 4));
+        // after the end of the line
+        assert_eq!(chars_in_columns(&line, 20, 22), (4, 4));
+    }
+
+    #[test]
+    fn test_convert() {
+        assert_eq!(Operator::Lowercase.convert("AbÇ1"), "abç1");
+        assert_eq!(Operator::Uppercase.convert("abß1"), "ABSS1");
+        assert_eq!(Operator::ToggleCase.convert("aB 1ç"), "Ab 1Ç");
+        assert_eq!(Operator::Replace('x').convert("ab\ncd"), "xx\nxx");
+        assert_eq!(Operator::Delete.convert("aB"), "aB");
+    }
+}
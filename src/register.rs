@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use operator::Yanked;
+
+// yanked texts kept in the history registers `0` to `9`
+const HISTORY_SIZE: usize = 10;
+
+// the texts deleted, changed or yanked, to be put later
+#[derive(Debug, Clone)]
+pub struct Registers {
+    // `"`, the last one
+    unnamed: Option<Yanked>,
+    // `a` to `z`
+    named: HashMap<char, Yanked>,
+    // `0` to `9`, the newest first
+    history: Vec<Yanked>,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers {
+            unnamed: None,
+            named: HashMap::new(),
+            history: vec![],
+        }
+    }
+}
+
+// the clipboard of the system, `*` is the same as `+`
+pub fn is_clipboard(name: char) -> bool {
+    name == '+' || name == '*'
+}
+
+pub fn is_valid_name(name: char) -> bool {
+    name == '"' || name.is_ascii_alphanumeric() || is_clipboard(name)
+}
+
+impl Registers {
+    // stores `yanked` in the register `name` (`A` to `Z` append to `a` to `z`),
+    // and always in the unnamed register and the history
+    pub fn store(&mut self, name: Option<char>, yanked: Yanked) {
+        match name {
+            Some(name) if name.is_ascii_lowercase() => {
+                self.named.insert(name, yanked.clone());
+            }
+            Some(name) if name.is_ascii_uppercase() => {
+                let appended = match self.named.remove(&name.to_ascii_lowercase()) {
+                    // lines appended to chars begin at a new line
+                    Some(mut old) => {
+                        if yanked.is_linewise && !old.text.ends_with('\n') {
+                            old.text.push('\n');
+                        }
+                        old.text.push_str(yanked.text.as_str());
+                        // chars appended to lines make a line too
+                        let is_linewise = old.is_linewise || yanked.is_linewise;
+                        if is_linewise && !old.text.ends_with('\n') {
+                            old.text.push('\n');
+                        }
+                        Yanked {
+                            text: old.text,
+                            is_linewise: is_linewise,
+                        }
+                    }
+                    None => yanked.clone(),
+                };
+                self.named.insert(name.to_ascii_lowercase(), appended);
+            }
+            _ => (),
+        }
+        self.history.insert(0, yanked.clone());
+        self.history.truncate(HISTORY_SIZE);
+        self.unnamed = Some(yanked);
+    }
+
+    // the unnamed register if `name` is `None`
+    pub fn get(&self, name: Option<char>) -> Option<&Yanked> {
+        match name {
+            None | Some('"') => self.unnamed.as_ref(),
+            Some(name) if name.is_ascii_digit() => {
+                self.history.get(name.to_digit(10).unwrap() as usize)
+            }
+            Some(name) if name.is_ascii_alphabetic() => self.named.get(&name.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+
+    // e.g. `"":foo "0:foo "a:bar^J`, newlines shown as `^J`
+    pub fn list(&self) -> String {
+        let mut items = vec![];
+        let mut push = |name: char, yanked: &Yanked| {
+            items.push(format!("\"{}:{}", name, yanked.text.replace('\n', "^J")));
+        };
+        if let Some(ref yanked) = self.unnamed {
+            push('"', yanked);
+        }
+        for (i, yanked) in self.history.iter().enumerate() {
+            push((b'0' + i as u8) as char, yanked);
+        }
+        let mut names: Vec<&char> = self.named.keys().collect();
+        names.sort();
+        for name in names {
+            push(*name, &self.named[name]);
+        }
+        items.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Yanked {
+        Yanked {
+            text: text.to_string(),
+            is_linewise: false,
+        }
+    }
+
+    fn lines(text: &str) -> Yanked {
+        Yanked {
+            text: text.to_string(),
+            is_linewise: true,
+        }
+    }
+
+    #[test]
+    fn test_store_and_get() {
+        let mut registers = Registers::default();
+        assert_eq!(registers.get(None), None);
+        registers.store(Some('a'), chars("foo"));
+        assert_eq!(registers.get(Some('a')), Some(&chars("foo")));
+        assert_eq!(registers.get(Some('A')), Some(&chars("foo")));
+        assert_eq!(registers.get(None), Some(&chars("foo")));
+        assert_eq!(registers.get(Some('"')), Some(&chars("foo")));
+        assert_eq!(registers.get(Some('0')), Some(&chars("foo")));
+        assert_eq!(registers.get(Some('b')), None);
+        assert_eq!(registers.get(Some('-')), None);
+        // the unnamed register and the history only
+        registers.store(None, chars("bar"));
+        assert_eq!(registers.get(Some('a')), Some(&chars("foo")));
+        assert_eq!(registers.get(None), Some(&chars("bar")));
+    }
+
+    #[test]
+    fn test_append() {
+        let append = |old: Yanked, new: Yanked| {
+            let mut registers = Registers::default();
+            registers.store(Some('a'), old);
+            registers.store(Some('A'), new.clone());
+            // the unnamed register has only the appended text
+            assert_eq!(registers.get(None), Some(&new));
+            registers.get(Some('a')).cloned()
+        };
+        assert_eq!(append(chars("foo"), chars("bar")), Some(chars("foobar")));
+        assert_eq!(
+            append(chars("foo"), lines("bar\n")),
+            Some(lines("foo\nbar\n"))
+        );
+        assert_eq!(
+            append(lines("foo\n"), chars("bar")),
+            Some(lines("foo\nbar\n"))
+        );
+        assert_eq!(
+            append(lines("foo\n"), lines("bar\n")),
+            Some(lines("foo\nbar\n"))
+        );
+
+        let mut registers = Registers::default();
+        registers.store(Some('B'), chars("foo"));
+        assert_eq!(registers.get(Some('b')), Some(&chars("foo")));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut registers = Registers::default();
+        for i in 0..12 {
+            registers.store(None, chars(i.to_string().as_str()));
+        }
+        // the newest first, up to 10
+        assert_eq!(registers.get(Some('0')), Some(&chars("11")));
+        assert_eq!(registers.get(Some('1')), Some(&chars("10")));
+        assert_eq!(registers.get(Some('9')), Some(&chars("2")));
+        assert_eq!(registers.history.len(), HISTORY_SIZE);
+    }
+}
@@ -0,0 +1,95 @@
+use regex::Regex;
+
+use buffer::Buffer;
+use cursor::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+impl SearchDirection {
+    pub fn reverse(self) -> Self {
+        match self {
+            SearchDirection::Forward => SearchDirection::Backward,
+            SearchDirection::Backward => SearchDirection::Forward,
+        }
+    }
+
+    pub fn mark(self) -> char {
+        match self {
+            SearchDirection::Forward => '/',
+            SearchDirection::Backward => '?',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub pattern: String,
+    pub direction: SearchDirection,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub cursor: Cursor,
+    pub is_wrapped: bool,
+}
+
+pub fn compile(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))
+}
+
+// (start, end) positions in chars of every match of `regex` in `line`
+pub fn find_in_line(line: &str, regex: &Regex) -> Vec<(usize, usize)> {
+    regex
+        .find_iter(line)
+        .map(|m| {
+            let start = line[..m.start()].chars().count();
+            let end = start + line[m.start()..m.end()].chars().count();
+            (start, end)
+        })
+        .collect()
+}
+
+// finds the nearest match of `regex` from `from` (exclusive) toward `direction`,
+// wrapping around at the end of the buffer
+pub fn find(
+    buffer: &Buffer,
+    regex: &Regex,
+    from: &Cursor,
+    direction: SearchDirection,
+) -> Option<Match> {
+    let height = buffer.height();
+    if height == 0 {
+        return None;
+    }
+    let from_y = if from.y < height { from.y } else { height - 1 };
+
+    // the line of `from` is visited twice: once for the part after `from`,
+    // and once (after wrapping) for the part before it
+    for i in 0..height + 1 {
+        let (y, is_wrapped) = match direction {
+            SearchDirection::Forward => ((from_y + i) % height, from_y + i >= height),
+            SearchDirection::Backward => ((from_y + height - i % height) % height, i > from_y),
+        };
+        let line: String = buffer.line_at(y).into_iter().collect();
+        let mut xs = find_in_line(&line, regex)
+            .into_iter()
+            .map(|(start, _)| start);
+        let x = match (direction, i) {
+            (SearchDirection::Forward, 0) => xs.find(|x| *x > from.x),
+            (SearchDirection::Backward, 0) => xs.rev().find(|x| *x < from.x),
+            (SearchDirection::Forward, _) => xs.next(),
+            (SearchDirection::Backward, _) => xs.next_back(),
+        };
+        if let Some(x) = x {
+            return Some(Match {
+                cursor: Cursor { x: x, y: y },
+                is_wrapped: is_wrapped,
+            });
+        }
+    }
+    None
+}
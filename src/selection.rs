@@ -0,0 +1,145 @@
+use std::cmp::{max, min};
+
+use buffer::Buffer;
+use cursor::Cursor;
+use operator::{self, Range};
+use viewport;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VisualKind {
+    Char,
+    Line,
+    Block,
+}
+
+// the text selected in Visual Mode, from `anchor` to the cursor of the current panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub kind: VisualKind,
+    pub anchor: Cursor,
+}
+
+impl Selection {
+    // the range operators apply to, which includes the chars at both ends
+    pub fn range(&self, buffer: &Buffer, cursor: &Cursor) -> Range {
+        let (start, end) = if operator::is_before(cursor, &self.anchor) {
+            (cursor, &self.anchor)
+        } else {
+            (&self.anchor, cursor)
+        };
+        match self.kind {
+            VisualKind::Char => {
+                let end = Cursor {
+                    x: min(end.x, buffer.line_width_at(end.y)),
+                    y: end.y,
+                };
+                Range::Chars(start.clone(), operator::next_position(buffer, &end))
+            }
+            VisualKind::Line => Range::Lines(start.y, end.y),
+            VisualKind::Block => {
+                let (anchor_left, anchor_right) = columns_of(buffer, &self.anchor);
+                let (cursor_left, cursor_right) = columns_of(buffer, cursor);
+                Range::Block {
+                    first: start.y,
+                    last: end.y,
+                    left: min(anchor_left, cursor_left),
+                    right: max(anchor_right, cursor_right),
+                }
+            }
+        }
+    }
+}
+
+// display columns taken by the char at `cursor`, from and up to (excluding).
+// the end of a line takes a column
+fn columns_of(buffer: &Buffer, cursor: &Cursor) -> (usize, usize) {
+    let line = buffer.line_at(cursor.y);
+    let positions = viewport::char_positions(&line, 0, false);
+    let x = min(cursor.x, line.len());
+    let (left, _) = positions[x];
+    match positions.get(x + 1) {
+        Some(&(right, _)) => (left, max(right, left + 1)),
+        None => (left, left + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer_of(text: &str) -> Buffer {
+        let mut buffer = Buffer::empty();
+        buffer.push(text.to_string());
+        buffer
+    }
+
+    #[test]
+    fn test_columns_of() {
+        let buffer = buffer_of("a\tb漢c\n漢字\tx");
+        let columns = |x, y| columns_of(&buffer, &Cursor { x: x, y: y });
+        assert_eq!(columns(0, 0), (0, 1));
+        assert_eq!(columns(1, 0), (1, 8));
+        assert_eq!(columns(3, 0), (9, 11));
+        assert_eq!(columns(4, 0), (11, 12));
+        // the end of a line, and after it
+        assert_eq!(columns(5, 0), (12, 13));
+        assert_eq!(columns(9, 0), (12, 13));
+        assert_eq!(columns(1, 1), (2, 4));
+        assert_eq!(columns(2, 1), (4, 8));
+    }
+
+    #[test]
+    fn test_range() {
+        let buffer = buffer_of("a\tb漢c\n漢字\tx\nabcdefghijkl");
+        let range = |kind, anchor: (usize, usize), cursor: (usize, usize)| {
+            let selection = Selection {
+                kind: kind,
+                anchor: Cursor {
+                    x: anchor.0,
+                    y: anchor.1,
+                },
+            };
+            selection.range(
+                &buffer,
+                &Cursor {
+                    x: cursor.0,
+                    y: cursor.1,
+                },
+            )
+        };
+        // a block covers whole wide chars and tabs at both ends
+        let block = range(VisualKind::Block, (3, 0), (2, 2));
+        assert_eq!(
+            block,
+            Range::Block {
+                first: 0,
+                last: 2,
+                left: 2,
+                right: 11,
+            }
+        );
+        assert_eq!(block.yank(&buffer).text, "\tb漢\n字\tx\ncdefghijk");
+        let block = range(VisualKind::Block, (1, 1), (1, 0));
+        assert_eq!(
+            block,
+            Range::Block {
+                first: 0,
+                last: 1,
+                left: 1,
+                right: 8,
+            }
+        );
+        assert_eq!(block.yank(&buffer).text, "\t\n漢字\t");
+
+        // the char at the end is included, the end of a line is its newline
+        assert_eq!(
+            range(VisualKind::Char, (4, 0), (1, 1)),
+            Range::Chars(Cursor { x: 4, y: 0 }, Cursor { x: 2, y: 1 })
+        );
+        assert_eq!(
+            range(VisualKind::Char, (9, 0), (3, 0)),
+            Range::Chars(Cursor { x: 3, y: 0 }, Cursor { x: 0, y: 1 })
+        );
+        assert_eq!(range(VisualKind::Line, (9, 2), (3, 0)), Range::Lines(0, 2));
+    }
+}
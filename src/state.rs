@@ -1,11 +1,34 @@
+use std::cmp::min;
 use std::collections::HashMap;
 
-use buffer::{Buffer, BufferId};
+use encoding_rs::Encoding;
+
+use buffer::{Buffer, BufferId, FileFormat};
+use clipboard;
+use config;
 use cursor::Cursor;
-use frame::Frame;
+use error::Result;
+use frame::{Frame, Size};
 use layout::{Layout, Panel, PanelName};
+use motion::{self, Motion};
+use operator::{self, Operator, Range, Yanked};
+use register::{self, Registers};
+use search::{self, Search};
+use selection::Selection;
 use status::Status;
+use substitute::{LineRange, Substitute};
 use util::Direction;
+use viewport::{self, Alignment, Viewport};
+
+// width of the encoding and line endings at the right of the status bar
+const STATUS_INFO_WIDTH: usize = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScrollUnit {
+    Line,
+    HalfPage,
+    Page,
+}
 
 #[derive(Clone, Debug)]
 pub struct State {
@@ -13,74 +36,129 @@ pub struct State {
     pub layout: Layout,
     pub current_panel_name: PanelName,
     pub status: Status,
+    pub search: Option<Search>,
+    pub is_search_highlighted: bool,
+    pub registers: Registers,
+    // text for the terminal to copy, written by the drawer
+    pub clipboard_out: Option<String>,
+    // in Visual Mode
+    pub selection: Option<Selection>,
     pub is_quit: bool,
 }
 
 impl State {
-    pub fn from_file(filename: &str) -> Self {
+    // opens every file in `filenames` and shows the first one,
+    // or an empty scratch buffer if no file is given.
+    // `encoding` ... the encoding of the files, detected if `None`
+    pub fn from_files(filenames: &[String], encoding: Option<&'static Encoding>) -> Result<Self> {
         let mut buffers = HashMap::new();
 
-        let body_buffer = Buffer::from_file(filename);
-        let body_buffer_id = BufferId::new();
-        buffers.insert(body_buffer_id, body_buffer);
+        let mut body_buffer_ids = vec![];
+        for filename in filenames {
+            let buffer_id = BufferId::new();
+            buffers.insert(buffer_id, Buffer::open(filename, encoding)?);
+            body_buffer_ids.push(buffer_id);
+        }
+        let body_buffer_id = match body_buffer_ids.first() {
+            Some(buffer_id) => *buffer_id,
+            None => {
+                let buffer_id = BufferId::new();
+                buffers.insert(buffer_id, Buffer::empty());
+                buffer_id
+            }
+        };
+        let body_panel_name = match filenames.first() {
+            Some(filename) => PanelName::new(filename),
+            None => PanelName::new("__scratch__"),
+        };
 
-        let ((mode_buffer, mode_buffer_id), (msg_buffer, msg_buffer_id)) = Buffer::status_buffer();
+        let (
+            (mode_buffer, mode_buffer_id),
+            (msg_buffer, msg_buffer_id),
+            (info_buffer, info_buffer_id),
+        ) = Buffer::status_buffer();
         buffers.insert(mode_buffer_id, mode_buffer);
         buffers.insert(msg_buffer_id, msg_buffer);
+        buffers.insert(info_buffer_id, info_buffer);
 
         let status = Status {
             mode_buffer_id: mode_buffer_id,
             msg_buffer_id: msg_buffer_id,
+            info_buffer_id: info_buffer_id,
         };
 
         let body_panel = Panel {
             cursor: Cursor::default(),
-            path: Some(filename.to_string()),
+            viewport: Viewport::default(),
             buffer_id: body_buffer_id,
             is_visible_line_number: false,
             enable_syntax_highlight: true,
+            is_wrapped: config::wrap(),
         };
 
         let status_mode_panel = Panel {
             cursor: Cursor::default(),
-            path: None,
+            viewport: Viewport::default(),
             buffer_id: mode_buffer_id,
             is_visible_line_number: false,
             enable_syntax_highlight: false,
+            is_wrapped: false,
         };
         let status_msg_panel = Panel {
             cursor: Cursor::default(),
-            path: None,
+            viewport: Viewport::default(),
             buffer_id: msg_buffer_id,
             is_visible_line_number: false,
             enable_syntax_highlight: false,
+            is_wrapped: false,
+        };
+        let status_info_panel = Panel {
+            cursor: Cursor::default(),
+            viewport: Viewport::default(),
+            buffer_id: info_buffer_id,
+            is_visible_line_number: false,
+            enable_syntax_highlight: false,
+            is_wrapped: false,
         };
 
         let layout = Layout::Lined(
             Direction::Down,
-            1,
+            Size::Fixed(1),
             Box::new(Layout::Lined(
                 Direction::Left,
-                6,
+                Size::Fixed(6),
                 Box::new(Layout::Panel(
                     status_mode_panel,
                     PanelName::new("__status_mode__"),
                 )),
-                Box::new(Layout::Panel(
-                    status_msg_panel,
-                    PanelName::new("__status_msg__"),
+                Box::new(Layout::Lined(
+                    Direction::Right,
+                    Size::Fixed(STATUS_INFO_WIDTH),
+                    Box::new(Layout::Panel(
+                        status_info_panel,
+                        PanelName::new("__status_info__"),
+                    )),
+                    Box::new(Layout::Panel(
+                        status_msg_panel,
+                        PanelName::new("__status_msg__"),
+                    )),
                 )),
             )),
-            Box::new(Layout::Panel(body_panel, PanelName::new(filename))),
+            Box::new(Layout::Panel(body_panel, body_panel_name.clone())),
         );
 
-        State {
+        Ok(State {
             buffers: buffers,
             layout: layout,
             status: status,
-            current_panel_name: PanelName::new(filename),
+            current_panel_name: body_panel_name,
+            search: None,
+            is_search_highlighted: false,
+            registers: Registers::default(),
+            clipboard_out: None,
+            selection: None,
             is_quit: false,
-        }
+        })
     }
 
     pub fn current_panel_with_frame(&self) -> (&Panel, Frame) {
@@ -125,6 +203,29 @@ impl State {
             .push(mode);
     }
 
+    // encoding and line endings of the current buffer
+    pub fn update_info(&mut self) {
+        let info = {
+            let buffer = self.current_buffer();
+            if buffer.is_binary() {
+                "binary".to_string()
+            } else {
+                let bom = if buffer.has_bom() { " [BOM]" } else { "" };
+                format!(
+                    "{}{} {}",
+                    buffer.encoding().name(),
+                    bom,
+                    buffer.file_format()
+                )
+            }
+        };
+        self.buffers
+            .get_mut(&self.status.info_buffer_id)
+            .expect("internal error: missing status info buffer")
+            .clear()
+            .push(info);
+    }
+
     pub fn update_message(&mut self, msg: &str) {
         self.buffers
             .get_mut(&self.status.msg_buffer_id)
@@ -133,18 +234,739 @@ impl State {
             .push(msg.to_string());
     }
 
-    pub fn clamp_cursor(&mut self) {
-        let current_buffer_height = self.current_buffer().height();
+    // buffers shown in body panels, in the order they were opened
+    pub fn body_buffer_ids(&self) -> Vec<BufferId> {
+        let mut buffer_ids: Vec<BufferId> = self
+            .buffers
+            .keys()
+            .filter(|buffer_id| !self.status.is_status_buffer(**buffer_id))
+            .cloned()
+            .collect();
+        buffer_ids.sort();
+        buffer_ids
+    }
+
+    pub fn show_buffer(&mut self, buffer_id: BufferId) {
+        let panel = self.current_panel_mut();
+        if panel.buffer_id != buffer_id {
+            panel.buffer_id = buffer_id;
+            panel.cursor = Cursor::default();
+            panel.viewport = Viewport::default();
+        }
+    }
+
+    // returns the buffer of `path`, loading it if it is not opened yet
+    fn load_buffer(&mut self, path: &str) -> Option<BufferId> {
+        let opened = self
+            .buffers
+            .iter()
+            .find(|(_, buffer)| buffer.path() == Some(path))
+            .map(|(buffer_id, _)| *buffer_id);
+        if opened.is_some() {
+            return opened;
+        }
+        match Buffer::open(path, None) {
+            Ok(buffer) => {
+                let buffer_id = BufferId::new();
+                self.buffers.insert(buffer_id, buffer);
+                Some(buffer_id)
+            }
+            Err(err) => {
+                self.update_message(format!("{}", err).as_str());
+                None
+            }
+        }
+    }
+
+    pub fn open_buffer(&mut self, path: &str) {
+        if let Some(buffer_id) = self.load_buffer(path) {
+            self.show_buffer(buffer_id);
+        }
+    }
+
+    // e.g. `%1:"a.rs" 2:"b.rs" [+]`, `%` marks the current buffer and `[+]` modified ones
+    pub fn buffer_list(&self) -> String {
+        let current_buffer_id = self.current_panel().buffer_id;
+        let items: Vec<String> = self
+            .body_buffer_ids()
+            .into_iter()
+            .enumerate()
+            .map(|(i, buffer_id)| {
+                let buffer = &self.buffers[&buffer_id];
+                format!(
+                    "{}{}:\"{}\"{}",
+                    if buffer_id == current_buffer_id {
+                        "%"
+                    } else {
+                        ""
+                    },
+                    i + 1,
+                    buffer.name(),
+                    if buffer.is_modified() { " [+]" } else { "" },
+                )
+            })
+            .collect();
+        items.join(" ")
+    }
+
+    // shows the `n`-th (1-origin) buffer of the buffer list
+    pub fn show_nth_buffer(&mut self, n: usize) {
+        let buffer_ids = self.body_buffer_ids();
+        match n.checked_sub(1).and_then(|i| buffer_ids.get(i)) {
+            Some(buffer_id) => self.show_buffer(*buffer_id),
+            None => self.update_message(format!("no such buffer: {}", n).as_str()),
+        }
+    }
+
+    // shows the buffer `offset` next to the current one, cyclically
+    pub fn cycle_buffer(&mut self, offset: isize) {
+        let buffer_ids = self.body_buffer_ids();
+        let current_buffer_id = self.current_panel().buffer_id;
+        let len = buffer_ids.len() as isize;
+        if let Some(i) = buffer_ids.iter().position(|id| *id == current_buffer_id) {
+            let next = ((i as isize + offset) % len + len) % len;
+            self.show_buffer(buffer_ids[next as usize]);
+        }
+    }
+
+    pub fn delete_current_buffer(&mut self, is_forced: bool) {
+        let deleted = self.current_panel().buffer_id;
+        if !is_forced && self.current_buffer().is_modified() {
+            self.update_message("unsaved changes, use :write or :bdelete! to discard them");
+            return;
+        }
+
+        let buffer_ids = self.body_buffer_ids();
+        let pos = buffer_ids
+            .iter()
+            .position(|id| *id == deleted)
+            .expect("internal error: missing buffer");
+        let next = if buffer_ids.len() == 1 {
+            let buffer_id = BufferId::new();
+            self.buffers.insert(buffer_id, Buffer::empty());
+            buffer_id
+        } else if pos + 1 < buffer_ids.len() {
+            buffer_ids[pos + 1]
+        } else {
+            buffer_ids[pos - 1]
+        };
+
+        // every panel showing the deleted buffer shows the next one instead
+        let _ = self.layout.traverse_mut::<(), ()>(&|panel, _, _| {
+            if panel.buffer_id == deleted {
+                panel.buffer_id = next;
+                panel.cursor = Cursor::default();
+                panel.viewport = Viewport::default();
+            }
+            Err(())
+        });
+        self.buffers.remove(&deleted);
+    }
+
+    // splits the current panel into two, the new one at `dir` side shows
+    // the buffer of `path` (or the current buffer) and gets focused
+    pub fn split_panel(&mut self, dir: Direction, path: Option<&str>) {
+        let buffer_id = match path {
+            Some(path) => match self.load_buffer(path) {
+                Some(buffer_id) => buffer_id,
+                None => return,
+            },
+            None => self.current_panel().buffer_id,
+        };
+        let (current_panel, frame) = self.current_panel_with_frame();
+        if frame.length(&dir) < 2 {
+            self.update_message("no room for a new panel");
+            return;
+        }
+        let new_panel = Panel {
+            cursor: if buffer_id == current_panel.buffer_id {
+                current_panel.cursor.clone()
+            } else {
+                Cursor::default()
+            },
+            buffer_id: buffer_id,
+            ..current_panel.clone()
+        };
+        let new_panel_name = PanelName::fresh();
+        self.layout.split_panel(
+            &self.current_panel_name,
+            dir,
+            Size::Ratio(1, 2),
+            new_panel,
+            new_panel_name.clone(),
+        );
+        self.current_panel_name = new_panel_name;
+    }
+
+    pub fn resize_current_panel(&mut self, delta: isize) {
+        if !self.layout.resize_panel(&self.current_panel_name, delta) {
+            self.update_message("current panel can not be resized");
+        }
+    }
+
+    pub fn close_current_panel(&mut self) {
+        let n_body_panels = self
+            .layout
+            .panels()
+            .into_iter()
+            .filter(|(panel, _, _)| !self.status.is_status_buffer(panel.buffer_id))
+            .count();
+        if n_body_panels <= 1 {
+            self.update_message("can not close the last panel");
+            return;
+        }
+        if let Some(panel_name) = self.layout.close_panel(&self.current_panel_name) {
+            self.current_panel_name = panel_name;
+        }
+    }
+
+    // focuses the nearest panel at `dir` side of the current panel
+    pub fn focus_panel(&mut self, dir: Direction) {
+        let (_, current) = self.current_panel_with_frame();
+        let next = self
+            .layout
+            .panels()
+            .into_iter()
+            .filter(|(panel, _, _)| !self.status.is_status_buffer(panel.buffer_id))
+            .filter_map(|(_, panel_name, frame)| {
+                let overlaps_x =
+                    frame.x < current.x + current.width && current.x < frame.x + frame.width;
+                let overlaps_y =
+                    frame.y < current.y + current.height && current.y < frame.y + frame.height;
+                // distance to `frame`, and whether it faces the current panel
+                let (distance, is_facing) = match dir {
+                    Direction::Up if frame.y + frame.height <= current.y => {
+                        (current.y - (frame.y + frame.height), overlaps_x)
+                    }
+                    Direction::Down if current.y + current.height <= frame.y => {
+                        (frame.y - (current.y + current.height), overlaps_x)
+                    }
+                    Direction::Left if frame.x + frame.width <= current.x => {
+                        (current.x - (frame.x + frame.width), overlaps_y)
+                    }
+                    Direction::Right if current.x + current.width <= frame.x => {
+                        (frame.x - (current.x + current.width), overlaps_y)
+                    }
+                    _ => return None,
+                };
+                Some(((!is_facing, distance), panel_name.clone()))
+            })
+            .min_by_key(|(key, _)| *key)
+            .map(|(_, panel_name)| panel_name);
+        match next {
+            Some(panel_name) => self.current_panel_name = panel_name,
+            None => self.update_message(format!("no panel at {:?}", dir).as_str()),
+        }
+    }
+
+    // focuses the panel `offset` next to the current one, cyclically
+    pub fn cycle_panel(&mut self, offset: isize) {
+        let panel_names: Vec<PanelName> = self
+            .layout
+            .panels()
+            .into_iter()
+            .filter(|(panel, _, _)| !self.status.is_status_buffer(panel.buffer_id))
+            .map(|(_, panel_name, _)| panel_name.clone())
+            .collect();
+        let len = panel_names.len() as isize;
+        if let Some(i) = panel_names
+            .iter()
+            .position(|panel_name| panel_name == &self.current_panel_name)
+        {
+            let next = ((i as isize + offset) % len + len) % len;
+            self.current_panel_name = panel_names[next as usize].clone();
+        }
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.buffers.values().any(|buffer| buffer.is_modified())
+    }
+
+    // refused while any buffer has unsaved changes
+    pub fn quit(&mut self) {
+        if self.is_modified() {
+            self.update_message("unsaved changes, use :write or :quit! to discard them");
+        } else {
+            self.is_quit = true;
+        }
+    }
+
+    // binary buffers are edited only in Hex Edit Mode.
+    // returns whether the current buffer is binary, telling so
+    pub fn refuse_edit(&mut self) -> bool {
+        if self.current_buffer().is_binary() {
+            self.update_message("binary buffer is read-only, use :hex-edit to overwrite bytes");
+            return true;
+        }
+        // the loaded text may end in the middle of a line
+        if self.current_buffer().is_loading() {
+            self.update_message("buffer is still loading, edit it when it is done");
+            return true;
+        }
+        false
+    }
+
+    // `option` ... `name`, `name=value` or `noname` as in `:set`
+    pub fn set_option(&mut self, option: &str) {
+        // bytes of binary buffers are written as they are
+        if self.current_buffer().is_binary() {
+            self.update_message("binary buffer has no options");
+            return;
+        }
+        let (name, value) = match option.find('=') {
+            Some(pos) => (&option[..pos], Some(&option[pos + 1..])),
+            None => (option, None),
+        };
+        match (name, value) {
+            ("fileformat", None) | ("ff", None) => {
+                let msg = format!("fileformat={}", self.current_buffer().file_format());
+                self.update_message(msg.as_str());
+            }
+            ("fileformat", Some(value)) | ("ff", Some(value)) => {
+                match value.parse::<FileFormat>() {
+                    Ok(file_format) => self.current_buffer_mut().set_file_format(file_format),
+                    Err(msg) => self.update_message(msg.as_str()),
+                }
+            }
+            ("fileencoding", None) | ("fenc", None) => {
+                let msg = format!("fileencoding={}", self.current_buffer().encoding().name());
+                self.update_message(msg.as_str());
+            }
+            ("fileencoding", Some(value)) | ("fenc", Some(value)) => {
+                match Encoding::for_label(value.as_bytes()) {
+                    Some(encoding) => self.current_buffer_mut().set_encoding(encoding),
+                    None => {
+                        self.update_message(format!("invalid fileencoding: {}", value).as_str())
+                    }
+                }
+            }
+            ("bomb", None) if !self.current_buffer().can_have_bom() => {
+                let encoding = self.current_buffer().encoding().name();
+                self.update_message(format!("{} has no BOM", encoding).as_str())
+            }
+            ("bomb", None) => self.current_buffer_mut().set_bom(true),
+            ("nobomb", None) => self.current_buffer_mut().set_bom(false),
+            ("endofline", None) | ("eol", None) => {
+                self.current_buffer_mut().set_final_newline(true)
+            }
+            ("noendofline", None) | ("noeol", None) => {
+                self.current_buffer_mut().set_final_newline(false)
+            }
+            _ => self.update_message(format!("unknown option: {}", option).as_str()),
+        }
+    }
+
+    // takes the parts of files scanned in the background. returns whether anything changed
+    pub fn poll_loading(&mut self) -> bool {
+        let mut is_changed = false;
+        let mut messages = vec![];
+        for buffer in self.buffers.values_mut() {
+            if !buffer.is_loading() {
+                continue;
+            }
+            match buffer.poll_loading() {
+                Ok(is_grown) => is_changed |= is_grown,
+                Err(err) => {
+                    messages.push(format!(
+                        "{}, only the first {} lines are loaded",
+                        err,
+                        buffer.height()
+                    ));
+                    is_changed = true;
+                }
+            }
+        }
+        for message in messages {
+            self.update_message(message.as_str());
+        }
+        is_changed
+    }
+
+    // returns whether the current buffer was written to its path
+    pub fn write_current_buffer(&mut self) -> bool {
+        let path = match self.current_buffer().path() {
+            Some(path) => path.to_string(),
+            None => {
+                self.update_message("no file name, use :save-as <filename>");
+                return false;
+            }
+        };
+        match self.current_buffer_mut().save_as(&path) {
+            Ok(()) => {
+                self.update_message(format!("\"{}\" written", path).as_str());
+                true
+            }
+            Err(err) => {
+                self.update_message(format!("{}", err).as_str());
+                false
+            }
+        }
+    }
+
+    // moves the cursor to the next match of the last search,
+    // or to the previous one if `is_reversed`
+    pub fn search_next(&mut self, is_reversed: bool) {
+        let search = match self.search {
+            Some(ref search) => search.clone(),
+            None => {
+                self.update_message("no previous search pattern");
+                return;
+            }
+        };
+        let direction = if is_reversed {
+            search.direction.reverse()
+        } else {
+            search.direction
+        };
+        let regex = match search::compile(&search.pattern) {
+            Ok(regex) => regex,
+            Err(msg) => {
+                self.update_message(msg.as_str());
+                return;
+            }
+        };
+        self.is_search_highlighted = true;
+        let cursor = self.current_panel().cursor.clone();
+        match search::find(self.current_buffer(), &regex, &cursor, direction) {
+            Some(found) => {
+                self.current_panel_mut().cursor = found.cursor;
+                if found.is_wrapped {
+                    self.update_message("search wrapped around");
+                } else {
+                    self.update_message(format!("{}{}", direction.mark(), search.pattern).as_str());
+                }
+            }
+            None => {
+                self.update_message(format!("pattern not found: {}", search.pattern).as_str());
+            }
+        }
+    }
+
+    pub fn substitute(&mut self, substitute: &Substitute) {
+        if self.refuse_edit() {
+            return;
+        }
+        let cursor = self.current_panel().cursor.clone();
+        let last = self.current_buffer().height() - 1;
+        let (first, last) = match substitute.range {
+            LineRange::Current => (cursor.y, cursor.y),
+            LineRange::Lines(first, last_) => (first, ::std::cmp::min(last_, last)),
+            LineRange::Whole => (0, last),
+        };
+
+        let mut n_substitutions = 0;
+        let mut n_lines = 0;
+        let mut last_substituted_line = None;
+        {
+            let buffer = self.current_buffer_mut();
+            // the whole substitution is undone at once
+            buffer.begin_undo_group(&cursor);
+            // from the bottom so that lines split by `\n` do not shift the rest
+            for y in (first..last + 1).rev() {
+                let line: String = buffer.line_at(y).into_iter().collect();
+                if let Some((replaced, n)) = substitute.apply(&line) {
+                    buffer.replace_line(y, &replaced, &cursor);
+                    n_substitutions += n;
+                    n_lines += 1;
+                    if last_substituted_line.is_none() {
+                        last_substituted_line = Some(y);
+                    }
+                }
+            }
+            buffer.end_undo_group();
+        }
+
+        match last_substituted_line {
+            Some(y) => {
+                self.current_panel_mut().cursor = Cursor { x: 0, y: y };
+                self.update_message(
+                    format!("{} substitutions on {} lines", n_substitutions, n_lines).as_str(),
+                );
+            }
+            None => self.update_message(
+                format!("pattern not found: {}", substitute.regex.as_str()).as_str(),
+            ),
+        }
+    }
+
+    pub fn undo(&mut self) {
+        match self.current_buffer_mut().undo() {
+            Some(cursor) => self.current_panel_mut().cursor = cursor,
+            None => self.update_message("already at oldest change"),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        match self.current_buffer_mut().redo() {
+            Some(cursor) => self.current_panel_mut().cursor = cursor,
+            None => self.update_message("already at newest change"),
+        }
+    }
+
+    // `count` ... the count typed before the motion, if any
+    pub fn move_cursor(&mut self, motion: Motion, count: Option<usize>) {
+        let cursor = self.current_panel().cursor.clone();
+        if let Some(cursor) = motion.apply(self.current_buffer(), &cursor, count) {
+            self.current_panel_mut().cursor = cursor;
+        }
+    }
+
+    // stores `yanked` in the register `name`, copying it to the clipboard for `+`
+    pub fn store_register(&mut self, name: Option<char>, yanked: Yanked) {
+        match name {
+            Some(name) if register::is_clipboard(name) => match clipboard::copy(&yanked.text) {
+                Ok(true) => (),
+                Ok(false) => self.clipboard_out = Some(yanked.text.clone()),
+                Err(msg) => self.update_message(msg.as_str()),
+            },
+            _ => (),
+        }
+        self.registers.store(name, yanked);
+    }
+
+    // puts the text of the register `name` `count` times after the cursor,
+    // or before it if `is_before`. lines are put below or above the cursor line
+    pub fn put(&mut self, name: Option<char>, count: Option<usize>, is_before: bool) {
+        let yanked = match name {
+            Some(name) if register::is_clipboard(name) => match clipboard::paste() {
+                Ok(text) => Yanked {
+                    is_linewise: text.ends_with('\n'),
+                    text: text,
+                },
+                Err(msg) => {
+                    self.update_message(msg.as_str());
+                    return;
+                }
+            },
+            _ => match self.registers.get(name) {
+                Some(yanked) => yanked.clone(),
+                None => {
+                    let msg = format!("register \"{} is empty", name.unwrap_or('"'));
+                    self.update_message(msg.as_str());
+                    return;
+                }
+            },
+        };
+        if self.refuse_edit() {
+            return;
+        }
+        let text = yanked.text.repeat(count.unwrap_or(1));
+        let cursor = self.current_panel().cursor.clone();
+        let buffer = self.current_buffer_mut();
+        let next_cursor = if yanked.is_linewise {
+            let y = if is_before { cursor.y } else { cursor.y + 1 };
+            if y < buffer.height() {
+                let at = Cursor { x: 0, y: y };
+                buffer.replace_between(&at, &at, &text, &cursor);
+            } else {
+                // after the last line, which has no newline to put lines after
+                let at = Cursor {
+                    x: buffer.line_width_at(y - 1),
+                    y: y - 1,
+                };
+                let text = format!("\n{}", &text[..text.len() - 1]);
+                buffer.replace_between(&at, &at, &text, &cursor);
+            }
+            Cursor {
+                x: motion::first_non_blank(buffer, y),
+                y: y,
+            }
+        } else {
+            let at = if is_before {
+                cursor.clone()
+            } else {
+                Cursor {
+                    x: min(cursor.x + 1, buffer.line_width_at(cursor.y)),
+                    y: cursor.y,
+                }
+            };
+            buffer.replace_between(&at, &at, &text, &cursor);
+            at
+        };
+        self.current_panel_mut().cursor = next_cursor;
+    }
+
+    // applies `operator` to `range` of the current buffer as one change,
+    // storing the deleted, changed or yanked text in the register `register`.
+    // returns whether Edit Mode follows, as after `c`, which ends the change
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        range: Range,
+        register: Option<char>,
+    ) -> bool {
+        if operator != Operator::Yank && self.refuse_edit() {
+            return false;
+        }
+        let cursor = self.current_panel().cursor.clone();
+        let shift_width = config::shift_width();
+        let (first_line, last_line) = range.line_span();
+        let buffer_id = self.current_panel().buffer_id;
+        let buffer = self
+            .buffers
+            .get_mut(&buffer_id)
+            .expect("internal error: missing current buffer");
+        let yanked = range.yank(buffer);
+        // parts are edited from the bottom, so that editing one does not move the rest
+        let parts = range.parts(buffer);
+        let start = parts[0].0.clone();
+
+        buffer.begin_undo_group(&cursor);
+        let next_cursor = match operator {
+            Operator::Yank if range.is_linewise() => Cursor {
+                x: cursor.x,
+                y: first_line,
+            },
+            Operator::Yank => start,
+            Operator::Delete if range.is_linewise() => {
+                let (start, end) = range.delete_bounds(buffer);
+                buffer.replace_between(&start, &end, "", &cursor);
+                let y = min(first_line, buffer.height() - 1);
+                Cursor {
+                    x: motion::first_non_blank(buffer, y),
+                    y: y,
+                }
+            }
+            // changed lines are left empty to type in
+            Operator::Delete | Operator::Change => {
+                for (start, end) in parts.iter().rev() {
+                    buffer.replace_between(start, end, "", &cursor);
+                }
+                start
+            }
+            Operator::Indent | Operator::Dedent => {
+                for y in first_line..last_line + 1 {
+                    let line: String = buffer.line_at(y).into_iter().collect();
+                    let shifted = operator::shift_line(&line, operator, shift_width);
+                    if shifted != line {
+                        buffer.replace_line(y, &shifted, &cursor);
+                    }
+                }
+                Cursor {
+                    x: motion::first_non_blank(buffer, first_line),
+                    y: first_line,
+                }
+            }
+            Operator::Lowercase
+            | Operator::Uppercase
+            | Operator::ToggleCase
+            | Operator::Replace(_) => {
+                for (start, end) in parts.iter().rev() {
+                    let text = buffer.text_between(start, end);
+                    let converted = operator.convert(&text);
+                    if converted != text {
+                        buffer.replace_between(start, end, &converted, &cursor);
+                    }
+                }
+                start
+            }
+        };
+        if operator != Operator::Change {
+            buffer.end_undo_group();
+        }
+
+        match operator {
+            Operator::Delete | Operator::Change | Operator::Yank => {
+                self.store_register(register, yanked)
+            }
+            _ => (),
+        }
+        self.current_panel_mut().cursor = next_cursor;
+        operator == Operator::Change
+    }
+
+    // moves the cursor by `n` screen rows, which differ from lines
+    // only when the current panel wraps long lines
+    pub fn go_screen_rows(&mut self, dir: Direction, n: usize) {
+        let (panel, frame) = self.current_panel_with_frame();
+        let buffer = self.current_buffer();
+        let width = panel.text_width(&frame, buffer);
+        let mut cursor = panel.cursor.clone();
+        if !panel.wraps(buffer) || width == 0 {
+            cursor.go(dir, n);
+        } else {
+            let positions_of =
+                |line_i: usize| viewport::char_positions(&buffer.line_at(line_i), width, true);
+            for _ in 0..n {
+                let positions = positions_of(cursor.y);
+                let (col, row) = positions[min(cursor.x, positions.len() - 1)];
+                let last_row = positions[positions.len() - 1].1;
+                match dir {
+                    Direction::Up if row > 0 => {
+                        cursor.x = viewport::char_index_at(&positions, col, row - 1)
+                    }
+                    Direction::Up if cursor.y > 0 => {
+                        cursor.y -= 1;
+                        let positions = positions_of(cursor.y);
+                        let last_row = positions[positions.len() - 1].1;
+                        cursor.x = viewport::char_index_at(&positions, col, last_row);
+                    }
+                    Direction::Down if row < last_row => {
+                        cursor.x = viewport::char_index_at(&positions, col, row + 1)
+                    }
+                    Direction::Down if cursor.y + 1 < buffer.height() => {
+                        cursor.y += 1;
+                        cursor.x = viewport::char_index_at(&positions_of(cursor.y), col, 0);
+                    }
+                    Direction::Left | Direction::Right => cursor.go(dir, 1),
+                    _ => break,
+                }
+            }
+        }
+        self.current_panel_mut().cursor = cursor;
+    }
+
+    fn update_current_panel(&mut self, f: &dyn Fn(&mut Panel, &Frame, &Buffer)) {
+        let buffer_id = self.current_panel().buffer_id;
+        let ref buffer = self.buffers[&buffer_id];
         let ref current_panel_name = self.current_panel_name;
-        self.layout
+        let _ = self
+            .layout
             .traverse_mut::<(), ()>(&|panel, panel_name, frame| {
-                if &panel_name == &current_panel_name {
-                    panel.fix_cursor_pos(frame.width, current_buffer_height);
+                if panel_name == current_panel_name {
+                    f(panel, frame, buffer);
+                    Ok(())
+                } else {
+                    Err(())
                 }
-                Err(())
             });
     }
 
+    // scrolls the current panel by `n` units, up if negative
+    pub fn scroll(&mut self, n: isize, unit: ScrollUnit) {
+        let margin = config::scroll_margin();
+        self.update_current_panel(&|panel, frame, buffer| {
+            let lines = match unit {
+                ScrollUnit::Line => n,
+                ScrollUnit::HalfPage => n * (frame.height / 2) as isize,
+                // two lines are kept on the screen
+                ScrollUnit::Page => n * frame.height.saturating_sub(2) as isize,
+            };
+            panel.scroll(lines, frame, buffer, margin);
+        });
+    }
+
+    pub fn align_cursor_line(&mut self, alignment: Alignment) {
+        let margin = config::scroll_margin();
+        self.update_current_panel(&|panel, frame, _| {
+            panel.align_cursor_line(alignment, frame, margin);
+        });
+    }
+
+    // every panel is clamped since panels can share a buffer
+    pub fn clamp_cursor(&mut self) {
+        let margin = config::scroll_margin();
+        let ref buffers = self.buffers;
+        let _ = self.layout.traverse_mut::<(), ()>(&|panel, _, frame| {
+            let buffer = buffers
+                .get(&panel.buffer_id)
+                .expect("internal error: missing buffer");
+            panel.fix_cursor_pos(buffer);
+            panel.follow_cursor(frame, buffer, margin);
+            Err(())
+        });
+    }
+
     pub fn current_buffer(&self) -> &Buffer {
         let buffer_id = self.current_panel().buffer_id;
         self.buffers
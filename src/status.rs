@@ -4,4 +4,13 @@ use buffer::BufferId;
 pub struct Status {
     pub mode_buffer_id: BufferId,
     pub msg_buffer_id: BufferId,
+    pub info_buffer_id: BufferId,
+}
+
+impl Status {
+    pub fn is_status_buffer(&self, buffer_id: BufferId) -> bool {
+        buffer_id == self.mode_buffer_id
+            || buffer_id == self.msg_buffer_id
+            || buffer_id == self.info_buffer_id
+    }
 }
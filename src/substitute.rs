@@ -0,0 +1,211 @@
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineRange {
+    Current,
+    //    first  last   (0-origin, inclusive)
+    Lines(usize, usize),
+    Whole,
+}
+
+#[derive(Debug, Clone)]
+pub struct Substitute {
+    pub range: LineRange,
+    pub regex: Regex,
+    pub replacement: String,
+    pub is_global: bool,
+}
+
+// `.`, `$` or 1-origin line number
+fn parse_line_address(s: &str, current: usize, last: usize) -> Result<usize, String> {
+    match s {
+        "." => Ok(current),
+        "$" => Ok(last),
+        s => match s.parse::<usize>() {
+            Ok(n) if n >= 1 => Ok(n - 1),
+            _ => Err(format!("invalid line number: {}", s)),
+        },
+    }
+}
+
+fn parse_range(s: &str, current: usize, last: usize) -> Result<LineRange, String> {
+    let addresses: Vec<&str> = s.split(',').collect();
+    match addresses.as_slice() {
+        [""] => Ok(LineRange::Current),
+        ["%"] => Ok(LineRange::Whole),
+        [line] => {
+            let line = parse_line_address(line, current, last)?;
+            Ok(LineRange::Lines(line, line))
+        }
+        [first, last_] => {
+            let first = parse_line_address(first, current, last)?;
+            let last_ = parse_line_address(last_, current, last)?;
+            if first <= last_ {
+                Ok(LineRange::Lines(first, last_))
+            } else {
+                Err(format!("backwards range: {}", s))
+            }
+        }
+        _ => Err(format!("invalid range: {}", s)),
+    }
+}
+
+// splits `s` at unescaped `/`, turning `\/` into `/`
+fn split_at_delimiters(s: &str) -> Vec<String> {
+    let mut result = vec![String::new()];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('/') => result.last_mut().unwrap().push('/'),
+                Some(c) => {
+                    let last = result.last_mut().unwrap();
+                    last.push('\\');
+                    last.push(c);
+                }
+                None => result.last_mut().unwrap().push('\\'),
+            },
+            '/' => result.push(String::new()),
+            c => result.last_mut().unwrap().push(c),
+        }
+    }
+    result
+}
+
+// converts `\1`..`\9`, `\0` and `&` into the `${n}` syntax of `regex`,
+// and `\\`, `\&` and `$` into literal characters
+fn convert_replacement(s: &str) -> String {
+    let mut result = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(n) if n.is_ascii_digit() => result.push_str(format!("${{{}}}", n).as_str()),
+                Some('n') => result.push('\n'),
+                Some('$') => result.push_str("$$"),
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            },
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+impl Substitute {
+    // parses `[range]s/pattern/replacement/[flags]` (without the leading `:`),
+    // returns `None` if `command` is not a substitution at all.
+    // `current` and `last` are the 0-origin current and last line numbers
+    pub fn parse(command: &str, current: usize, last: usize) -> Option<Result<Self, String>> {
+        let s_pos = command.find("s/")?;
+        let (range, body) = command.split_at(s_pos);
+        if !range
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ',' || c == '%' || c == '.' || c == '$')
+        {
+            return None;
+        }
+        Some(Self::parse_impl(range, &body[2..], current, last))
+    }
+
+    fn parse_impl(range: &str, body: &str, current: usize, last: usize) -> Result<Self, String> {
+        let range = parse_range(range, current, last)?;
+        let parts = split_at_delimiters(body);
+        let (pattern, replacement, flags) = match parts.as_slice() {
+            [pattern] => (pattern.as_str(), "", ""),
+            [pattern, replacement] => (pattern.as_str(), replacement.as_str(), ""),
+            [pattern, replacement, flags] => {
+                (pattern.as_str(), replacement.as_str(), flags.as_str())
+            }
+            _ => return Err("usage :[range]s/pattern/replacement/[flags]".to_string()),
+        };
+
+        let mut is_global = false;
+        let mut is_ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => is_global = true,
+                'i' => is_ignore_case = true,
+                c => return Err(format!("invalid flag: {}", c)),
+            }
+        }
+
+        let pattern = if is_ignore_case {
+            format!("(?i){}", pattern)
+        } else {
+            pattern.to_string()
+        };
+        let regex = Regex::new(pattern.as_str()).map_err(|e| format!("invalid pattern: {}", e))?;
+
+        Ok(Substitute {
+            range: range,
+            regex: regex,
+            replacement: convert_replacement(replacement),
+            is_global: is_global,
+        })
+    }
+
+    // returns the substituted line and the number of substitutions,
+    // or `None` if nothing matched
+    pub fn apply(&self, line: &str) -> Option<(String, usize)> {
+        let count = self.regex.find_iter(line).count();
+        if count == 0 {
+            return None;
+        }
+        let (limit, count) = if self.is_global { (0, count) } else { (1, 1) };
+        let replaced = self
+            .regex
+            .replacen(line, limit, self.replacement.as_str())
+            .into_owned();
+        Some((replaced, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("", 4, 9), Ok(LineRange::Current));
+        assert_eq!(parse_range("%", 4, 9), Ok(LineRange::Whole));
+        assert_eq!(parse_range("3", 4, 9), Ok(LineRange::Lines(2, 2)));
+        assert_eq!(parse_range("2,5", 4, 9), Ok(LineRange::Lines(1, 4)));
+        assert_eq!(parse_range(".,$", 4, 9), Ok(LineRange::Lines(4, 9)));
+        assert!(parse_range("5,2", 4, 9).is_err());
+        assert!(parse_range("0", 4, 9).is_err());
+        assert!(parse_range("1,2,3", 4, 9).is_err());
+    }
+
+    #[test]
+    fn test_split_at_delimiters() {
+        assert_eq!(split_at_delimiters("a/b/g"), vec!["a", "b", "g"]);
+        assert_eq!(split_at_delimiters("a\\/b/c"), vec!["a/b", "c"]);
+        assert_eq!(split_at_delimiters("a\\d/"), vec!["a\\d", ""]);
+        assert_eq!(split_at_delimiters("a\\"), vec!["a\\"]);
+    }
+
+    #[test]
+    fn test_convert_replacement() {
+        assert_eq!(convert_replacement("\\1-\\2"), "${1}-${2}");
+        assert_eq!(convert_replacement("[&]"), "[${0}]");
+        assert_eq!(convert_replacement("\\&\\\\"), "&\\");
+        assert_eq!(convert_replacement("cost $5"), "cost $$5");
+        assert_eq!(convert_replacement("a$b\\$"), "a$$b$$");
+    }
+
+    #[test]
+    fn test_apply() {
+        let substitute = Substitute::parse("s/x/cost $5 &/g", 0, 0).unwrap().unwrap();
+        assert_eq!(
+            substitute.apply("x, x"),
+            Some(("cost $5 x, cost $5 x".to_string(), 2))
+        );
+        let substitute = Substitute::parse("s/(a)(b)/\\2\\1/", 0, 0)
+            .unwrap()
+            .unwrap();
+        assert_eq!(substitute.apply("abab"), Some(("baab".to_string(), 1)));
+    }
+}
@@ -1,5 +1,6 @@
 use serde::de::{Deserialize, Deserializer};
 use serde_derive::{Deserialize, Serialize};
+use unicode_width::UnicodeWidthChar;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub struct Rgb(pub u8, pub u8, pub u8);
@@ -62,3 +63,46 @@ pub fn clamp<T: Ord>(v: T, min: T, max: T) -> T {
         v
     }
 }
+
+pub fn base64(bytes: &[u8]) -> String {
+    const TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(TABLE[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+// columns between tab stops
+pub const TAB_WIDTH: usize = 8;
+
+// columns taken by `c` at the column `col` of a line in a terminal.
+// a tab reaches the next tab stop, other control characters are shown by `char_display`
+pub fn char_width(c: char, col: usize) -> usize {
+    match c {
+        '\t' => TAB_WIDTH - col % TAB_WIDTH,
+        c if c.is_control() => char_display(c, col).len(),
+        c => c.width().unwrap_or(1),
+    }
+}
+
+// how `c` at the column `col` is shown: spaces for a tab, `^X` for
+// other C0 control characters (`^?` for DEL), `<xx>` for C1 ones
+pub fn char_display(c: char, col: usize) -> String {
+    match c {
+        '\t' => " ".repeat(char_width(c, col)),
+        c if c < ' ' || c == '\x7f' => format!("^{}", (c as u8 ^ 0x40) as char),
+        c if c.is_control() => format!("<{:02x}>", c as u32),
+        c => c.to_string(),
+    }
+}
@@ -0,0 +1,204 @@
+use std::cmp::{max, min};
+
+use buffer::Buffer;
+use cursor::Cursor;
+use util::{char_width, clamp};
+
+// the part of a buffer shown in a panel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Viewport {
+    pub top_line: usize,
+    pub left_column: usize,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            top_line: 0,
+            left_column: 0,
+        }
+    }
+}
+
+// where to put the cursor line in `Viewport::align`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+// column and width of `c` put at `col` of a row `width` columns wide,
+// and whether it goes to the next row since it does not fit if `is_wrapped`
+pub fn place_char(c: char, col: usize, width: usize, is_wrapped: bool) -> (usize, usize, bool) {
+    let width_at = |col: usize| {
+        let c_width = char_width(c, col);
+        // a tab stops at the end of a wrapped row
+        if is_wrapped && c == '\t' {
+            min(c_width, max(width.saturating_sub(col), 1))
+        } else {
+            c_width
+        }
+    };
+    let c_width = width_at(col);
+    if is_wrapped && col > 0 && col + c_width > width {
+        (0, width_at(0), true)
+    } else {
+        (col, c_width, false)
+    }
+}
+
+// screen position (column, row) of every char of `line` and of the end of it.
+// if `is_wrapped`, a char which does not fit in the rest of a row goes to the next row
+pub fn char_positions(line: &[char], width: usize, is_wrapped: bool) -> Vec<(usize, usize)> {
+    let is_wrapped = is_wrapped && width > 0;
+    let mut result = Vec::with_capacity(line.len() + 1);
+    let (mut col, mut row) = (0, 0);
+    for c in line {
+        let (c_col, c_width, is_new_row) = place_char(*c, col, width, is_wrapped);
+        if is_new_row {
+            row += 1;
+        }
+        result.push((c_col, row));
+        col = c_col + c_width;
+    }
+    // just after the end of a line which fills the last row
+    if is_wrapped && col >= width {
+        result.push((width - 1, row));
+    } else {
+        result.push((col, row));
+    }
+    result
+}
+
+// index of the char at `col` of `row` (or the nearest one before it)
+pub fn char_index_at(positions: &[(usize, usize)], col: usize, row: usize) -> usize {
+    let mut result = None;
+    for (i, &(c, r)) in positions.iter().enumerate() {
+        if r == row && c <= col && result.map_or(true, |(_, best)| best < c) {
+            result = Some((i, c));
+        }
+    }
+    result.map_or(positions.len() - 1, |(i, _)| i)
+}
+
+// screen rows taken by the line `line_i` of `buffer` in `width` columns
+pub fn rows_of(buffer: &Buffer, line_i: usize, width: usize, is_wrapped: bool) -> usize {
+    if !is_wrapped || width == 0 {
+        return 1;
+    }
+    let positions = char_positions(&buffer.line_at(line_i), width, true);
+    positions.last().map_or(1, |&(_, row)| row + 1)
+}
+
+// lines the cursor keeps away from the top and bottom, at most a half of `height`
+fn effective_margin(margin: usize, height: usize) -> usize {
+    min(margin, height.saturating_sub(1) / 2)
+}
+
+impl Viewport {
+    // scrolls only if `cursor` comes within `margin` lines of the top or bottom,
+    // or goes out of `width` columns
+    pub fn follow(
+        &mut self,
+        cursor: &Cursor,
+        buffer: &Buffer,
+        width: usize,
+        height: usize,
+        margin: usize,
+        is_wrapped: bool,
+    ) {
+        let margin = effective_margin(margin, height);
+        if cursor.y < self.top_line + margin {
+            self.top_line = cursor.y.saturating_sub(margin);
+        }
+        let bottom = min(cursor.y + margin, buffer.height().saturating_sub(1));
+        if height > 0 && self.top_line + height <= bottom {
+            self.top_line = bottom + 1 - height;
+        }
+        // wrapped lines take several rows
+        if is_wrapped {
+            let rows_to = |top_line: usize| -> usize {
+                (top_line..bottom + 1)
+                    .map(|line_i| rows_of(buffer, line_i, width, true))
+                    .sum()
+            };
+            while self.top_line < cursor.y && rows_to(self.top_line) > height {
+                self.top_line += 1;
+            }
+        }
+
+        if is_wrapped {
+            self.left_column = 0;
+            return;
+        }
+        // columns are counted in display width
+        let line = buffer.line_at(cursor.y);
+        let positions = char_positions(&line, width, false);
+        let (col, _) = positions[min(cursor.x, line.len())];
+        let cursor_width = line.get(cursor.x).map_or(1, |c| char_width(*c, col));
+        if col < self.left_column {
+            self.left_column = col;
+        } else if width > 0 && self.left_column + width < col + cursor_width {
+            self.left_column = (col + cursor_width).saturating_sub(width);
+        }
+    }
+
+    // scrolls by `n` lines (up if negative), dragging `cursor` to stay in the viewport
+    pub fn scroll(
+        &mut self,
+        n: isize,
+        cursor: &mut Cursor,
+        buffer: &Buffer,
+        height: usize,
+        margin: usize,
+    ) {
+        let last_line = buffer.height().saturating_sub(1);
+        self.top_line = clamp(self.top_line as isize + n, 0, last_line as isize) as usize;
+
+        // the margins are not kept at the beginning and the end of the buffer
+        let margin = effective_margin(margin, height);
+        let min_y = if self.top_line == 0 {
+            0
+        } else {
+            self.top_line + margin
+        };
+        let max_y = if self.top_line + height > last_line {
+            last_line
+        } else {
+            (self.top_line + height).saturating_sub(margin + 1)
+        };
+        cursor.y = clamp(cursor.y, min_y, max(min_y, max_y));
+    }
+
+    // scrolls so that the line of `cursor` comes to `alignment` of the viewport
+    pub fn align(&mut self, alignment: Alignment, cursor: &Cursor, height: usize, margin: usize) {
+        let margin = effective_margin(margin, height);
+        self.top_line = match alignment {
+            Alignment::Top => cursor.y.saturating_sub(margin),
+            Alignment::Center => cursor.y.saturating_sub(height / 2),
+            Alignment::Bottom => (cursor.y + margin + 1).saturating_sub(height),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_positions_of_control_chars() {
+        // a tab reaches the next tab stop, `\x01` is shown as `^A`
+        let line: Vec<char> = "a\tb\x01c\r".chars().collect();
+        assert_eq!(
+            char_positions(&line, 80, false),
+            vec![(0, 0), (1, 0), (8, 0), (9, 0), (11, 0), (12, 0), (14, 0)]
+        );
+        // a tab stops at the end of a wrapped row
+        let line: Vec<char> = "abcdef\tg".chars().collect();
+        assert_eq!(
+            char_positions(&line, 7, true)[6..],
+            [(6, 0), (0, 1), (1, 1)]
+        );
+    }
+}